@@ -0,0 +1,124 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compares the number of allocations made when writing many small frames with and without a
+//! `BufferPool`.
+//!
+//! Run with `cargo bench -p ratchet_core --bench buffer_pool`.
+
+use bytes::BytesMut;
+use ratchet_core::{
+    BufferPool, NegotiatedExtension, NoExt, PayloadType, Role, WebSocket, WebSocketConfig,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+const FRAMES: usize = 100_000;
+const PAYLOAD: &[u8] = b"a small message";
+
+/// An allocator that counts the number of allocations that are made.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// A stream which discards everything that is written to it and never produces any data.
+struct Discard;
+
+impl AsyncRead for Discard {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for Discard {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+async fn write_frames(pool: Option<BufferPool>) -> (usize, Duration) {
+    let mut websocket = WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        Discard,
+        NegotiatedExtension::<NoExt>::from(None),
+        BytesMut::new(),
+        Role::Client,
+    );
+    websocket.set_buffer_pool(pool);
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        websocket
+            .write(PAYLOAD, PayloadType::Binary)
+            .await
+            .expect("Write failure");
+    }
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        start.elapsed(),
+    )
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let (allocations, elapsed) = write_frames(None).await;
+    println!(
+        "without pool: {:>8} allocations, {:>10.2?}/frame",
+        allocations,
+        elapsed / FRAMES as u32
+    );
+
+    let (allocations, elapsed) = write_frames(Some(BufferPool::new(8, 64 * 1024))).await;
+    println!(
+        "with pool:    {:>8} allocations, {:>10.2?}/frame",
+        allocations,
+        elapsed / FRAMES as u32
+    );
+}
@@ -0,0 +1,126 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compares writing large server frames using `WebSocket::write`, which copies the payload, with
+//! `WebSocket::write_frame_bytes`, which writes the payload directly.
+//!
+//! Run with `cargo bench -p ratchet_core --bench write_frame`.
+
+use bytes::{Bytes, BytesMut};
+use ratchet_core::{
+    FragmentKind, NegotiatedExtension, NoExt, PayloadType, Role, WebSocket, WebSocketConfig,
+};
+use std::io;
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+const ITERATIONS: u32 = 1_000;
+const PAYLOAD_SIZES: [usize; 3] = [64 * 1024, 1024 * 1024, 8 * 1024 * 1024];
+
+/// A stream which discards everything that is written to it and never produces any data.
+struct Discard;
+
+impl AsyncRead for Discard {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for Discard {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(bufs.iter().map(|buf| buf.len()).sum()))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn server() -> WebSocket<Discard, NoExt> {
+    WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        Discard,
+        NegotiatedExtension::from(None),
+        BytesMut::new(),
+        Role::Server,
+    )
+}
+
+async fn write_as_ref(payload: &Bytes) -> Duration {
+    let mut websocket = server();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        websocket
+            .write(payload, PayloadType::Binary)
+            .await
+            .expect("Write failure");
+    }
+    start.elapsed()
+}
+
+async fn write_frame_bytes(payload: &Bytes) -> Duration {
+    let mut websocket = server();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        websocket
+            .write_frame_bytes(payload.clone(), FragmentKind::Binary, true)
+            .await
+            .expect("Write failure");
+    }
+    start.elapsed()
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    for size in PAYLOAD_SIZES {
+        let payload = Bytes::from(vec![0u8; size]);
+
+        let as_ref = write_as_ref(&payload).await;
+        let bytes = write_frame_bytes(&payload).await;
+
+        println!(
+            "{:>8} bytes: write {:>10.2?}/frame, write_frame_bytes {:>10.2?}/frame",
+            size,
+            as_ref / ITERATIONS,
+            bytes / ITERATIONS,
+        );
+    }
+}
@@ -14,10 +14,23 @@
 
 use crate::errors::Error;
 use crate::ext::NoExtProvider;
-use crate::handshake::{ProtocolRegistry, UpgradedServer};
-use crate::{subscribe_with, TryIntoRequest, UpgradedClient, WebSocketConfig, WebSocketStream};
+use crate::handshake::{
+    subscribe_with_key, HandshakeTranscript, OriginAllowlist, ProtocolRegistry, UpgradedServer,
+};
+use crate::resolver::SharedResolver;
+use crate::{
+    ErrorKind, HttpError, ProtocolError, Request, Resolver, TryIntoRequest, UpgradedClient,
+    WebSocketConfig, WebSocketResponse, WebSocketStream,
+};
+use http::header::{ORIGIN, SEC_WEBSOCKET_PROTOCOL};
+use http::{HeaderValue, StatusCode};
 use ratchet_ext::ExtensionProvider;
+use std::any::Any;
 use std::borrow::Cow;
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::sync::Arc;
+use tokio::net::TcpStream;
 
 /// A builder to construct WebSocket clients.
 ///
@@ -29,6 +42,11 @@ pub struct WebSocketClientBuilder<E> {
     config: Option<WebSocketConfig>,
     extension: E,
     subprotocols: ProtocolRegistry,
+    nodelay: bool,
+    key: Option<[u8; 16]>,
+    origin: Option<HeaderValue>,
+    resolver: SharedResolver,
+    on_handshake: Option<HandshakeObserver>,
 }
 
 impl Default for WebSocketClientBuilder<NoExtProvider> {
@@ -37,6 +55,11 @@ impl Default for WebSocketClientBuilder<NoExtProvider> {
             config: None,
             extension: NoExtProvider,
             subprotocols: ProtocolRegistry::default(),
+            nodelay: true,
+            key: None,
+            origin: None,
+            resolver: SharedResolver::default(),
+            on_handshake: None,
         }
     }
 }
@@ -57,15 +80,90 @@ impl<E> WebSocketClientBuilder<E> {
             config,
             extension,
             subprotocols,
+            nodelay,
+            key,
+            origin,
+            on_handshake,
+            ..
         } = self;
-        subscribe_with(
-            config.unwrap_or_default(),
+        let mut request = request.try_into_request()?;
+        if let Some(origin) = origin {
+            request.headers_mut().insert(ORIGIN, origin);
+        }
+        set_nodelay(&stream, nodelay)?;
+
+        let config = config.unwrap_or_default();
+        let mut upgraded = subscribe_with_key(
+            observed_config(config, &on_handshake),
             stream,
             request,
             &extension,
             subprotocols,
+            key,
         )
-        .await
+        .await?;
+        if let Some(on_handshake) = on_handshake {
+            upgraded.transcript = on_handshake.notify(upgraded.transcript, config, true);
+        }
+        Ok(upgraded)
+    }
+
+    /// Resolve the host in `request` using the builder's resolver, open a `TcpStream` to the first
+    /// address that accepts the connection and then execute a client handshake over it.
+    ///
+    /// If the request URI does not contain a port then the `ws` scheme's default port of 80 is
+    /// used.
+    ///
+    /// # Errors
+    /// Errors if the URI does not contain a host, if the scheme is not `ws`, if the host cannot be
+    /// resolved or connected to, or if the handshake fails. A `wss` URI is rejected as this does
+    /// not establish a TLS connection.
+    pub async fn connect<I>(
+        self,
+        request: I,
+    ) -> Result<UpgradedClient<TcpStream, E::Extension>, Error>
+    where
+        I: TryIntoRequest,
+        E: ExtensionProvider,
+    {
+        let request = request.try_into_request()?;
+        let uri = request.uri();
+        let host = uri.host().ok_or_else(|| {
+            Error::with_cause(
+                ErrorKind::Http,
+                HttpError::MalformattedUri(Some("Missing host".to_string())),
+            )
+        })?;
+        // Only plaintext connections are opened, so any other scheme is rejected even if a port
+        // is provided rather than silently connecting without TLS.
+        let port = match uri.scheme_str() {
+            Some("ws") => uri.port_u16().unwrap_or(80),
+            scheme => {
+                return Err(Error::with_cause(
+                    ErrorKind::Http,
+                    HttpError::MalformattedUri(Some(format!("Unsupported scheme: {:?}", scheme))),
+                ))
+            }
+        };
+        // IPv6 hosts are bracketed in URIs but not when resolved.
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        let mut last_error = None;
+        for addr in self.resolver.resolve(host, port).await? {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => return self.subscribe(stream, request).await,
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error
+            .unwrap_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No addresses resolved for {}", host),
+                )
+            })
+            .into())
     }
 
     /// Sets the configuration that will be used for the connection.
@@ -74,6 +172,17 @@ impl<E> WebSocketClientBuilder<E> {
         self
     }
 
+    /// Sets the resolver that [`WebSocketClientBuilder::connect`] uses to look up the addresses of
+    /// a host, instead of the system's DNS. This is useful for custom service discovery,
+    /// split-horizon DNS or pointing hostnames at local addresses in tests.
+    pub fn resolver<R>(mut self, resolver: R) -> Self
+    where
+        R: Resolver,
+    {
+        self.resolver = SharedResolver::new(resolver);
+        self
+    }
+
     /// Sets the extension that will be used for the connection.
     pub fn extension<T>(self, extension: T) -> WebSocketClientBuilder<T>
     where
@@ -82,16 +191,29 @@ impl<E> WebSocketClientBuilder<E> {
         let WebSocketClientBuilder {
             config,
             subprotocols,
+            nodelay,
+            key,
+            origin,
+            resolver,
+            on_handshake,
             ..
         } = self;
         WebSocketClientBuilder {
             config,
             extension,
             subprotocols,
+            nodelay,
+            key,
+            origin,
+            resolver,
+            on_handshake,
         }
     }
 
     /// Sets the subprotocols that will be used for the connection.
+    ///
+    /// # Errors
+    /// Errors if any of the subprotocols are not a valid RFC7230 token.
     pub fn subprotocols<I>(mut self, subprotocols: I) -> Result<Self, Error>
     where
         I: IntoIterator,
@@ -100,6 +222,51 @@ impl<E> WebSocketClientBuilder<E> {
         self.subprotocols = ProtocolRegistry::new(subprotocols)?;
         Ok(self)
     }
+
+    /// Sets whether Nagle's algorithm will be disabled on the stream before the handshake is
+    /// executed. WebSocket messages are typically small and latency sensitive, so delaying them to
+    /// coalesce writes is rarely desirable. Defaults to `true`.
+    ///
+    /// This only applies to a `TcpStream` and has no effect on any other type of stream.
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Sets the `Origin` header that will be sent in the upgrade request, replacing any that the
+    /// request already contains.
+    ///
+    /// # Errors
+    /// Errors if `origin` is not a valid header value.
+    pub fn origin(mut self, origin: &str) -> Result<Self, Error> {
+        self.origin = Some(HeaderValue::from_str(origin)?);
+        Ok(self)
+    }
+
+    /// Sets a callback that is invoked with the raw bytes of the upgrade request that was sent and
+    /// the response that was received, once the handshake has completed successfully. This is
+    /// intended for logging or auditing the exact handshake exchange.
+    ///
+    /// The handshake is only captured if a callback is set or if
+    /// `WebSocketConfig::capture_handshake` is enabled.
+    pub fn on_handshake<F>(mut self, on_handshake: F) -> Self
+    where
+        F: Fn(&[u8], &[u8]) + Send + Sync + 'static,
+    {
+        self.on_handshake = Some(HandshakeObserver(Arc::new(on_handshake)));
+        self
+    }
+
+    /// Sets the key that will be sent in the `Sec-WebSocket-Key` header instead of a randomly
+    /// generated one. The server's `Sec-WebSocket-Accept` header is still verified against it.
+    ///
+    /// This is only intended for producing reproducible handshakes in tests; a fixed key must
+    /// not be used in production.
+    #[cfg(any(test, feature = "fixture"))]
+    pub fn fixed_key(mut self, key: [u8; 16]) -> Self {
+        self.key = Some(key);
+        self
+    }
 }
 
 /// A builder to construct WebSocket servers.
@@ -112,6 +279,10 @@ pub struct WebSocketServerBuilder<E> {
     config: Option<WebSocketConfig>,
     subprotocols: ProtocolRegistry,
     extension: E,
+    nodelay: bool,
+    origins: Option<OriginAllowlist>,
+    on_handshake: Option<HandshakeObserver>,
+    select_protocol: Option<ProtocolSelector>,
 }
 
 impl Default for WebSocketServerBuilder<NoExtProvider> {
@@ -120,6 +291,10 @@ impl Default for WebSocketServerBuilder<NoExtProvider> {
             config: None,
             extension: NoExtProvider,
             subprotocols: ProtocolRegistry::default(),
+            nodelay: true,
+            origins: None,
+            on_handshake: None,
+            select_protocol: None,
         }
     }
 }
@@ -135,10 +310,49 @@ impl<E> WebSocketServerBuilder<E> {
             config,
             subprotocols,
             extension,
+            nodelay,
+            origins,
+            on_handshake,
+            select_protocol,
         } = self;
-        let upgrader =
-            crate::accept_with(stream, config.unwrap_or_default(), extension, subprotocols).await?;
-        upgrader.upgrade().await
+        set_nodelay(&stream, nodelay)?;
+        let config = config.unwrap_or_default();
+        let mut upgrader = crate::accept_with(
+            stream,
+            observed_config(config, &on_handshake),
+            extension,
+            subprotocols,
+        )
+        .await?;
+
+        if let Some(origins) = origins {
+            if let Err(e) = origins.validate(upgrader.request()) {
+                upgrader
+                    .reject(WebSocketResponse::new(StatusCode::FORBIDDEN.as_u16())?)
+                    .await?;
+                return Err(e);
+            }
+        }
+
+        if let Some(select_protocol) = select_protocol {
+            match select_protocol.select(upgrader.request()) {
+                Ok(subprotocol) => upgrader.set_subprotocol(subprotocol),
+                Err(e) => {
+                    upgrader
+                        .reject(WebSocketResponse::new(
+                            StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                        )?)
+                        .await?;
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut upgraded = upgrader.upgrade().await?;
+        if let Some(on_handshake) = on_handshake {
+            upgraded.transcript = on_handshake.notify(upgraded.transcript, config, false);
+        }
+        Ok(upgraded)
     }
 
     /// Sets the configuration that will be used for the connection.
@@ -155,16 +369,27 @@ impl<E> WebSocketServerBuilder<E> {
         let WebSocketServerBuilder {
             config,
             subprotocols,
+            nodelay,
+            origins,
+            on_handshake,
+            select_protocol,
             ..
         } = self;
         WebSocketServerBuilder {
             config,
             extension,
             subprotocols,
+            nodelay,
+            origins,
+            on_handshake,
+            select_protocol,
         }
     }
 
     /// Sets the subprotocols that will be used for the connection.
+    ///
+    /// # Errors
+    /// Errors if any of the subprotocols are not a valid RFC7230 token.
     pub fn subprotocols<I>(mut self, subprotocols: I) -> Result<Self, Error>
     where
         I: IntoIterator,
@@ -173,4 +398,157 @@ impl<E> WebSocketServerBuilder<E> {
         self.subprotocols = ProtocolRegistry::new(subprotocols)?;
         Ok(self)
     }
+
+    /// Sets whether Nagle's algorithm will be disabled on the stream before the handshake is
+    /// executed. WebSocket messages are typically small and latency sensitive, so delaying them to
+    /// coalesce writes is rarely desirable. Defaults to `true`.
+    ///
+    /// This only applies to a `TcpStream` and has no effect on any other type of stream.
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Sets the origins that upgrade requests will be accepted from. If the `Origin` header of a
+    /// request does not match any of them then the connection is rejected with a
+    /// `403 Forbidden` response. Requests without an `Origin` header are accepted, as only
+    /// browsers are required to send one.
+    ///
+    /// Origins are compared ignoring ASCII case. An origin may use a wildcard in place of its
+    /// leftmost host labels, such as `https://*.example.com`, to allow any subdomain.
+    pub fn allowed_origins<I>(mut self, origins: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Cow<'static, str>>,
+    {
+        self.origins = Some(OriginAllowlist::new(origins));
+        self
+    }
+
+    /// Sets a callback that is invoked with the raw bytes of the upgrade request that was received
+    /// and the response that was sent, once the handshake has completed successfully. This is
+    /// intended for logging or auditing the exact handshake exchange.
+    ///
+    /// The handshake is only captured if a callback is set or if
+    /// `WebSocketConfig::capture_handshake` is enabled.
+    pub fn on_handshake<F>(mut self, on_handshake: F) -> Self
+    where
+        F: Fn(&[u8], &[u8]) + Send + Sync + 'static,
+    {
+        self.on_handshake = Some(HandshakeObserver(Arc::new(on_handshake)));
+        self
+    }
+
+    /// Sets a callback that selects the subprotocol for a connection, in place of the subprotocols
+    /// that were registered with this builder. The callback is invoked with the subprotocols that
+    /// the client offered and its upgrade request, allowing a subprotocol to be chosen based on
+    /// the request's path or headers. The returned subprotocol is sent to the client in the
+    /// `Sec-WebSocket-Protocol` header and returning `None` upgrades the connection without one.
+    ///
+    /// If the callback returns a subprotocol that the client did not offer then the connection is
+    /// rejected with a `500 Internal Server Error` response and the handshake fails.
+    pub fn select_protocol<F>(mut self, select_protocol: F) -> Self
+    where
+        F: Fn(&[&str], &Request) -> Option<String> + Send + Sync + 'static,
+    {
+        self.select_protocol = Some(ProtocolSelector(Arc::new(select_protocol)));
+        self
+    }
+}
+
+type SelectProtocolCallback = dyn Fn(&[&str], &Request) -> Option<String> + Send + Sync;
+
+/// A callback which selects the subprotocol for a connection.
+#[derive(Clone)]
+struct ProtocolSelector(Arc<SelectProtocolCallback>);
+
+impl Debug for ProtocolSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ProtocolSelector").finish()
+    }
+}
+
+impl ProtocolSelector {
+    /// Invokes the callback with the subprotocols offered in `request` and validates that the
+    /// selected subprotocol was one of them.
+    fn select(&self, request: &Request) -> Result<Option<String>, Error> {
+        let offered = request
+            .headers()
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .map(|value| {
+                value
+                    .to_str()
+                    .map_err(|_| Error::with_cause(ErrorKind::Protocol, ProtocolError::Encoding))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let offered = offered
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|protocol| !protocol.is_empty())
+            .collect::<Vec<_>>();
+
+        match (self.0)(&offered, request) {
+            Some(selected) if !offered.contains(&selected.as_str()) => Err(Error::with_cause(
+                ErrorKind::Protocol,
+                ProtocolError::UnknownProtocol,
+            )),
+            selected => Ok(selected),
+        }
+    }
+}
+
+type HandshakeCallback = dyn Fn(&[u8], &[u8]) + Send + Sync;
+
+/// A callback which observes the raw bytes of a handshake.
+#[derive(Clone)]
+struct HandshakeObserver(Arc<HandshakeCallback>);
+
+impl Debug for HandshakeObserver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("HandshakeObserver").finish()
+    }
+}
+
+impl HandshakeObserver {
+    /// Invokes the callback with the `(request, response)` of `transcript` and returns the
+    /// transcript if it was requested by `config`.
+    fn notify(
+        &self,
+        transcript: Option<HandshakeTranscript>,
+        config: WebSocketConfig,
+        is_client: bool,
+    ) -> Option<HandshakeTranscript> {
+        if let Some(transcript) = &transcript {
+            let HandshakeTranscript { sent, received } = transcript;
+            if is_client {
+                (self.0)(sent, received);
+            } else {
+                (self.0)(received, sent);
+            }
+        }
+        transcript.filter(|_| config.capture_handshake)
+    }
+}
+
+/// Returns `config` with handshake capturing enabled if there is a callback to observe it.
+fn observed_config(
+    mut config: WebSocketConfig,
+    on_handshake: &Option<HandshakeObserver>,
+) -> WebSocketConfig {
+    if on_handshake.is_some() {
+        config.capture_handshake = true;
+    }
+    config
+}
+
+fn set_nodelay<S>(stream: &S, nodelay: bool) -> Result<(), Error>
+where
+    S: WebSocketStream,
+{
+    if let Some(stream) = (stream as &dyn Any).downcast_ref::<TcpStream>() {
+        stream.set_nodelay(nodelay)?;
+    }
+    Ok(())
 }
@@ -94,6 +94,13 @@ impl Error {
         matches!(self.inner.kind, ErrorKind::Protocol)
     }
 
+    /// Returns the rule that was violated if this error was caused by the peer or the user
+    /// violating the WebSocket protocol, such as by sending a frame with an unnegotiated RSV bit
+    /// set or a text message that is not valid UTF-8.
+    pub fn protocol_error(&self) -> Option<&ProtocolError> {
+        self.downcast_ref()
+    }
+
     /// Whether this error is related to an encoding error.
     pub fn is_encoding(&self) -> bool {
         matches!(self.inner.kind, ErrorKind::Encoding)
@@ -103,6 +110,21 @@ impl Error {
     pub fn is_close(&self) -> bool {
         matches!(self.inner.kind, ErrorKind::Close)
     }
+
+    /// Whether the reader has likely lost its alignment with the frames being received.
+    pub fn is_desync(&self) -> bool {
+        matches!(self.inner.kind, ErrorKind::Desync)
+    }
+
+    /// Whether this error was produced by an operation timing out.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.inner.kind, ErrorKind::Timeout)
+    }
+
+    /// Whether this error was caused by the peer closing the connection without a close frame.
+    pub fn is_abnormal_close(&self) -> bool {
+        self.downcast_ref::<CloseCause>() == Some(&CloseCause::Abnormal)
+    }
 }
 
 #[derive(Debug)]
@@ -113,6 +135,7 @@ struct Inner {
 
 /// A type of error represented.
 #[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
 pub enum ErrorKind {
     /// An IO error.
     IO,
@@ -126,6 +149,12 @@ pub enum ErrorKind {
     Encoding,
     /// A close error.
     Close,
+    /// A frame header was received that could not have been produced by a conforming peer. This
+    /// is a best-effort indicator that the reader is no longer aligned to the start of a frame;
+    /// such as after a faulty extension has consumed too few or too many bytes.
+    Desync,
+    /// An operation did not complete within its configured duration.
+    Timeout,
 }
 
 impl From<io::Error> for Error {
@@ -167,15 +196,28 @@ pub enum HttpError {
     /// A request or response contained an invalid header.
     #[error("Invalid header: `{0}`")]
     InvalidHeader(HeaderName),
-    /// Sec-WebSocket-Key was invalid.
-    #[error("Sec-WebSocket-Accept mismatch")]
-    KeyMismatch,
+    /// Sec-WebSocket-Accept did not match the value computed from the Sec-WebSocket-Key that was
+    /// sent.
+    #[error("Sec-WebSocket-Accept mismatch. Expected: `{expected}`, received: `{received}`")]
+    KeyMismatch {
+        /// The accept value computed locally.
+        expected: String,
+        /// The accept value that the server responded with.
+        received: String,
+    },
     /// The provided URI was malformatted
     #[error("The provided URI was malformatted")]
     MalformattedUri(Option<String>),
     /// A provided header was malformatted
     #[error("A provided header was malformatted")]
     MalformattedHeader(String),
+    /// A subprotocol was not a valid [RFC7230](https://datatracker.ietf.org/doc/html/rfc7230#section-3.2.6)
+    /// token.
+    #[error("Invalid subprotocol: `{0}`. Subprotocols must be non-empty and may not contain separators or control characters")]
+    InvalidSubprotocol(String),
+    /// The `Origin` header of a request was not in the server's list of allowed origins.
+    #[error("Origin not allowed: `{0}`")]
+    DisallowedOrigin(String),
 }
 
 impl From<HttpError> for Error {
@@ -232,7 +274,7 @@ impl From<OpCodeParseErr> for ProtocolError {
 
 impl From<OpCodeParseErr> for Error {
     fn from(e: OpCodeParseErr) -> Self {
-        Error::with_cause(ErrorKind::Protocol, Box::new(ProtocolError::from(e)))
+        Error::with_cause(ErrorKind::Protocol, ProtocolError::from(e))
     }
 }
 
@@ -244,7 +286,7 @@ impl From<Utf8Error> for Error {
 
 impl From<CloseCodeParseErr> for Error {
     fn from(e: CloseCodeParseErr) -> Self {
-        Error::with_cause(ErrorKind::Protocol, e)
+        Error::with_cause(ErrorKind::Protocol, ProtocolError::CloseCode(e.0))
     }
 }
 
@@ -266,6 +308,10 @@ pub enum CloseCause {
     /// bug in your code.
     #[error("Attempted to use a closed channel")]
     Error,
+    /// The peer closed the underlying connection without completing a closing handshake. This
+    /// corresponds to the abnormal closure status code (1006) in RFC 6455.
+    #[error("The connection closed without a close frame")]
+    Abnormal,
 }
 
 /// WebSocket protocol errors.
@@ -292,6 +338,12 @@ pub enum ProtocolError {
     /// A received frame exceeded the maximum permitted size
     #[error("A frame exceeded the maximum permitted size")]
     FrameOverflow,
+    /// A control frame's payload exceeded the maximum of 125 bytes
+    #[error("A control frame exceeded the maximum permitted size")]
+    ControlFrameOverflow,
+    /// A received message was fragmented into more frames than are permitted
+    #[error("A message exceeded the maximum permitted number of fragments")]
+    TooManyFragments,
     /// A peer attempted to use an extension that has not been negotiated
     #[error("Attempted to use an extension that has not been negotiated")]
     UnknownExtension,
@@ -307,6 +359,9 @@ pub enum ProtocolError {
     /// Received unexpected control frame data
     #[error("Received unexpected control frame data")]
     ControlDataMismatch,
+    /// The first data message received was not of the expected type
+    #[error("The first message received was not of the expected type")]
+    UnexpectedOpCode,
 }
 
 impl From<FromUtf8Error> for Error {
@@ -17,8 +17,8 @@ use bytes::BytesMut;
 use http::{HeaderMap, HeaderValue};
 use httparse::Header;
 use ratchet_ext::{
-    Extension, ExtensionDecoder, ExtensionEncoder, ExtensionProvider, FrameHeader,
-    ReunitableExtension, RsvBits, SplittableExtension,
+    Extension, ExtensionDecoder, ExtensionEncoder, ExtensionProvider, ExtensionState,
+    ExtensionStats, FrameHeader, ReunitableExtension, RsvBits, SplittableExtension,
 };
 use std::convert::Infallible;
 
@@ -149,6 +149,21 @@ where
     }
 }
 
+impl<E> NegotiatedExtension<E> {
+    pub(crate) fn is_negotiated(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+impl<E> NegotiatedExtension<E>
+where
+    E: ExtensionState,
+{
+    pub(crate) fn state(&self) -> Option<E::State> {
+        self.0.as_ref().and_then(ExtensionState::save)
+    }
+}
+
 impl<E> From<Option<E>> for NegotiatedExtension<E>
 where
     E: Extension,
@@ -217,6 +232,20 @@ where
             },
         }
     }
+
+    fn stats(&self) -> Option<ExtensionStats> {
+        self.0.as_ref().and_then(Extension::stats)
+    }
+
+    fn reset_stats(&mut self) {
+        if let Some(ext) = &mut self.0 {
+            ext.reset_stats();
+        }
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.0.as_ref().and_then(Extension::summary)
+    }
 }
 
 impl<E> SplittableExtension for NegotiatedExtension<E>
@@ -15,23 +15,31 @@
 #[cfg(test)]
 mod tests;
 
-use crate::errors::{Error, ErrorKind, ProtocolError};
+use crate::errors::{CloseCause, Error, ErrorKind, ProtocolError};
+use crate::handshake::with_timeout;
+use crate::pool::{BufferPool, PooledBuffer};
+#[cfg(feature = "split")]
+use crate::protocol::FragmentKind;
 use crate::protocol::{
-    apply_mask, CloseCode, CloseReason, ControlCode, DataCode, FrameHeader, HeaderFlags,
-    MessageType, OpCode, Role,
+    apply_mask, CloseCode, CloseReason, ControlCode, DataCode, EntropyKeyGen, FrameHeader,
+    HeaderFlags, MaskingKeyGen, MessageType, OpCode, OpCodeParseErr, Role,
 };
 use crate::protocol::{BorrowedFramePrinter, FramePrinter};
 use crate::WebSocketStream;
 use bytes::Buf;
-use bytes::{BufMut, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
 use either::Either;
 use log::trace;
-use rand::rngs::SmallRng;
-use rand::{Rng, SeedableRng};
 use ratchet_ext::{ExtensionDecoder, FrameHeader as ExtFrameHeader, OpCode as ExtOpCode};
 use std::convert::TryFrom;
 use std::fmt::{Debug, Formatter};
+#[cfg(feature = "split")]
+use std::io::IoSlice;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::Instant;
 
 #[derive(Debug, PartialEq)]
 pub enum Item {
@@ -42,14 +50,24 @@ pub enum Item {
     Close(Option<CloseReason>),
 }
 
+#[cfg(feature = "split")]
+pub enum FragmentItem {
+    Data(FragmentKind, BytesMut, bool),
+    Control(Item),
+}
+
 bitflags::bitflags! {
     pub struct CodecFlags: u8 {
         const R_CONT    = 0b0000_0001;
+        // If high then a server will accept unmasked frames
+        const ACCEPT_UNMASKED = 0b0000_0010;
         // If high 'text' else 'binary
         const CONT_TYPE = 0b0000_1000;
 
         // If high then `server` else `client
         const ROLE     = 0b0000_0100;
+        // If high then the payloads of text messages are validated as UTF-8
+        const VALIDATE_UTF8 = 0b1000_0000;
 
         // Below are the reserved bits used by the negotiated extensions
         const RSV1      = 0b0100_0000;
@@ -77,13 +95,33 @@ impl FrameDecoder {
         &mut self,
         buf: &mut BytesMut,
         is_server: bool,
+        accept_unmasked: bool,
         rsv_bits: u8,
         max_message_size: usize,
     ) -> Result<DecodeResult, Error> {
         loop {
             match self {
                 FrameDecoder::DecodingHeader => {
-                    match FrameHeader::read_from(buf, is_server, rsv_bits, max_message_size)? {
+                    let result = match FrameHeader::read_from(
+                        buf,
+                        is_server,
+                        accept_unmasked,
+                        rsv_bits,
+                        max_message_size,
+                    ) {
+                        Ok(result) => result,
+                        Err(e @ ProtocolError::OpCode(OpCodeParseErr::Reserved(_))) => {
+                            // Wait for the complete payload length before classifying the error so
+                            // that the result does not depend on how the header was segmented.
+                            return match implausible_length(buf, max_message_size) {
+                                Either::Left(true) => Err(Error::with_cause(ErrorKind::Desync, e)),
+                                Either::Left(false) => Err(e.into()),
+                                Either::Right(count) => Ok(DecodeResult::Incomplete(count)),
+                            };
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
+                    match result {
                         Either::Left((header, header_len, payload_len)) => {
                             *self = FrameDecoder::DecodingPayload(header, header_len, payload_len);
                         }
@@ -117,32 +155,143 @@ impl FrameDecoder {
     }
 }
 
+/// Returns whether the length of the frame header at the start of `buf` could not have been
+/// produced by a conforming peer, or the number of bytes that are required to determine this. Used,
+/// alongside a reserved OpCode, as a best-effort indicator that the reader is no longer aligned to
+/// the start of a frame.
+fn implausible_length(buf: &[u8], max_message_size: usize) -> Either<bool, usize> {
+    if buf.len() < 2 {
+        return Either::Right(2 - buf.len());
+    }
+
+    match buf[1] & 0x7F {
+        126 if buf.len() < 4 => Either::Right(4 - buf.len()),
+        126 => {
+            // rfc6455 § 5.2: the minimal number of bytes MUST be used to encode the length
+            Either::Left(u16::from_be_bytes([buf[2], buf[3]]) < 126)
+        }
+        127 if buf.len() < 10 => Either::Right(10 - buf.len()),
+        127 => {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&buf[2..10]);
+            let length = u64::from_be_bytes(bytes);
+
+            // rfc6455 § 5.2: the most significant bit MUST be 0
+            Either::Left(
+                length >> 63 != 0
+                    || length <= u64::from(u16::MAX)
+                    || length > max_message_size as u64,
+            )
+        }
+        _ => Either::Left(false),
+    }
+}
+
+/// Returns the limit that is enforced for a maximum message size of `max_message_size`, where a
+/// size of zero disables the limit.
+fn message_limit(max_message_size: usize) -> usize {
+    if max_message_size == 0 {
+        usize::MAX
+    } else {
+        max_message_size
+    }
+}
+
 pub struct ReadProps {
     pub is_server: bool,
+    pub accept_unmasked: bool,
     pub rsv_bits: u8,
     pub max_message_size: usize,
 }
 
+/// The number of raw bytes that have been read from and written to a stream, including frame
+/// headers. These are shared between the read and write halves of a connection.
+#[derive(Debug, Default)]
+pub struct ByteCounts {
+    read: AtomicU64,
+    written: AtomicU64,
+}
+
+impl ByteCounts {
+    fn add_read(&self, count: usize) {
+        self.read.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    fn add_written(&self, count: usize) {
+        self.written.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the number of bytes that have been read and written.
+    pub fn get(&self) -> (u64, u64) {
+        (
+            self.read.load(Ordering::Relaxed),
+            self.written.load(Ordering::Relaxed),
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct FramedRead {
     read_buffer: BytesMut,
     decoder: FrameDecoder,
+    counts: Arc<ByteCounts>,
+    // The length of the read buffer when the fragmented message currently being read was started.
+    message_start: usize,
+    // The maximum message size that applies to the message currently being read. Changes to the
+    // maximum message size only take effect from the next message.
+    message_limit: usize,
+    // Whether RSV1 was set on the first frame of the most recently read data message.
+    compressed: bool,
+    // The number of frames that have been read for the message currently being read.
+    fragments: usize,
+    // The maximum number of frames that a message may be reassembled from.
+    max_fragments: usize,
 }
 
 impl FramedRead {
-    pub fn new(read_buffer: BytesMut) -> FramedRead {
+    pub fn new(read_buffer: BytesMut, counts: Arc<ByteCounts>) -> FramedRead {
         FramedRead {
             read_buffer,
             decoder: FrameDecoder::default(),
+            counts,
+            message_start: 0,
+            message_limit: 0,
+            compressed: false,
+            fragments: 0,
+            max_fragments: usize::MAX,
+        }
+    }
+
+    pub fn set_max_fragments(&mut self, max_fragments: Option<usize>) {
+        self.max_fragments = match max_fragments {
+            Some(max) if max != 0 => max,
+            _ => usize::MAX,
+        };
+    }
+
+    /// Returns whether RSV1 was set on the most recently read data message. For the
+    /// permessage-deflate extension this indicates that the message was compressed.
+    pub fn last_message_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    #[cfg(feature = "split")]
+    pub fn counts(&self) -> &Arc<ByteCounts> {
+        &self.counts
+    }
+
+    /// Validates the payload of the text message that has just been read, if required.
+    fn validate_text(&self, flags: &CodecFlags, read_into: &BytesMut) -> Result<(), Error> {
+        if flags.contains(CodecFlags::VALIDATE_UTF8) {
+            std::str::from_utf8(&read_into[self.message_start..]).map_err(|_| invalid_utf8())?;
         }
+        Ok(())
     }
 
     async fn read_frame<I>(
         &mut self,
         io: &mut I,
-        is_server: bool,
-        rsv_bits: u8,
-        max_message_size: usize,
+        props: &ReadProps,
     ) -> Result<(FrameHeader, BytesMut), Error>
     where
         I: AsyncRead + Unpin,
@@ -150,14 +299,33 @@ impl FramedRead {
         let FramedRead {
             read_buffer,
             decoder,
+            counts,
+            ..
         } = self;
 
         loop {
-            match decoder.decode(read_buffer, is_server, rsv_bits, max_message_size)? {
+            let ReadProps {
+                is_server,
+                accept_unmasked,
+                rsv_bits,
+                max_message_size,
+            } = *props;
+            match decoder.decode(
+                read_buffer,
+                is_server,
+                accept_unmasked,
+                rsv_bits,
+                max_message_size,
+            )? {
                 DecodeResult::Incomplete(count) => {
-                    let len = read_buffer.len();
-                    read_buffer.resize(len + count, 0u8);
-                    io.read_exact(&mut read_buffer[len..]).await?;
+                    // Only the bytes that have been read are appended to the buffer and the
+                    // decoder's state is retained, so no data is lost if this future is dropped.
+                    read_buffer.reserve(count);
+                    let read = io.read_buf(&mut (&mut *read_buffer).limit(count)).await?;
+                    if read == 0 {
+                        return Err(Error::with_cause(ErrorKind::IO, CloseCause::Abnormal));
+                    }
+                    counts.add_read(read);
                 }
                 DecodeResult::Finished(header, payload) => return Ok((header, payload)),
             }
@@ -170,23 +338,21 @@ impl FramedRead {
         flags: &mut CodecFlags,
         read_into: &mut BytesMut,
         extension: &mut E,
-        props: ReadProps,
+        mut props: ReadProps,
     ) -> Result<Item, Error>
     where
         I: AsyncRead + Unpin,
         E: ExtensionDecoder,
     {
-        let ReadProps {
-            is_server,
-            rsv_bits,
-            max_message_size,
-        } = props;
+        if !flags.contains(CodecFlags::R_CONT) {
+            self.message_limit = props.max_message_size;
+        }
+        let max_message_size = self.message_limit;
+        props.max_message_size = max_message_size;
 
         loop {
-            let (header, payload) = self
-                .read_frame(io, is_server, rsv_bits, max_message_size)
-                .await?;
-            trace!("Read frame: {}", FramePrinter(&header));
+            let (header, payload) = self.read_frame(io, &props).await?;
+            trace!("Read frame: {}", FramePrinter(&header, payload.len()));
 
             match header.opcode {
                 OpCode::DataCode(data_code) => {
@@ -194,6 +360,17 @@ impl FramedRead {
                         return Err(ProtocolError::FrameOverflow.into());
                     }
 
+                    if !flags.contains(CodecFlags::R_CONT) {
+                        self.message_start = read_into.len();
+                        self.compressed = header.flags.is_rsv1();
+                        self.fragments = 0;
+                    }
+
+                    self.fragments += 1;
+                    if self.fragments > self.max_fragments {
+                        return Err(ProtocolError::TooManyFragments.into());
+                    }
+
                     read_into.put(payload);
 
                     match data_code {
@@ -208,6 +385,7 @@ impl FramedRead {
                                     )?;
 
                                     if flags.contains(CodecFlags::CONT_TYPE) {
+                                        self.validate_text(flags, read_into)?;
                                         Item::Text
                                     } else {
                                         Item::Binary
@@ -239,6 +417,7 @@ impl FramedRead {
                                     &header.flags,
                                     ExtOpCode::Text,
                                 )?;
+                                self.validate_text(flags, read_into)?;
                                 return Ok(Item::Text);
                             } else {
                                 flags.insert(CodecFlags::R_CONT | CodecFlags::CONT_TYPE);
@@ -277,85 +456,201 @@ impl FramedRead {
                     }
                 }
                 OpCode::ControlCode(c) => {
-                    return match c {
-                        ControlCode::Close => {
-                            let reason = if payload.len() < 2 {
-                                None
-                            } else {
-                                match CloseCode::try_from([payload[0], payload[1]])? {
-                                    close_code if close_code.is_illegal() => {
-                                        return Err(
-                                            ProtocolError::CloseCode(u16::from(close_code)).into()
-                                        )
-                                    }
-                                    close_code => {
-                                        let close_reason =
-                                            std::str::from_utf8(&payload[2..])?.to_string();
-                                        let description = if close_reason.is_empty() {
-                                            None
-                                        } else {
-                                            Some(close_reason)
-                                        };
-
-                                        let reason = CloseReason::new(close_code, description);
-
-                                        Some(reason)
-                                    }
-                                }
-                            };
+                    let item = decode_control(c, payload)?;
+                    if matches!(item, Item::Close(_)) && flags.contains(CodecFlags::R_CONT) {
+                        // The fragmented message that was being read will never be completed.
+                        read_into.truncate(self.message_start);
+                        flags.remove(CodecFlags::R_CONT | CodecFlags::CONT_TYPE);
+                    }
+                    return Ok(item);
+                }
+            }
+        }
+    }
 
-                            Ok(Item::Close(reason))
-                        }
-                        ControlCode::Ping => {
-                            if payload.len() > 125 {
-                                return Err(ProtocolError::FrameOverflow.into());
-                            } else {
-                                Ok(Item::Ping(payload))
-                            }
-                        }
-                        ControlCode::Pong => {
-                            if payload.len() > 125 {
-                                return Err(ProtocolError::FrameOverflow.into());
-                            } else {
-                                Ok(Item::Pong(payload))
-                            }
-                        }
-                    };
+    #[cfg(feature = "split")]
+    pub async fn read_fragment<I, E>(
+        &mut self,
+        io: &mut I,
+        flags: &mut CodecFlags,
+        extension: &mut E,
+        props: ReadProps,
+    ) -> Result<FragmentItem, Error>
+    where
+        I: AsyncRead + Unpin,
+        E: ExtensionDecoder,
+    {
+        let (header, mut payload) = self.read_frame(io, &props).await?;
+        trace!("Read frame: {}", FramePrinter(&header, payload.len()));
+
+        let data_code = match header.opcode {
+            OpCode::DataCode(data_code) => data_code,
+            OpCode::ControlCode(c) => {
+                let item = decode_control(c, payload)?;
+                if matches!(item, Item::Close(_)) {
+                    flags.remove(CodecFlags::R_CONT | CodecFlags::CONT_TYPE);
                 }
+                return Ok(FragmentItem::Control(item));
             }
+        };
+
+        // Extensions decode complete messages, so the payload of an individual frame that has been
+        // encoded by one cannot be decoded.
+        if header.flags.intersects(HeaderFlags::RESERVED) {
+            return Err(Error::with_cause(
+                ErrorKind::Extension,
+                "Cannot read the fragments of a message that has been encoded by an extension",
+            ));
         }
+
+        let fin = header.flags.contains(HeaderFlags::FIN);
+        let (kind, opcode) = match data_code {
+            DataCode::Continuation => {
+                if !flags.contains(CodecFlags::R_CONT) {
+                    return Err(ProtocolError::ContinuationNotStarted.into());
+                }
+                if fin {
+                    flags.remove(CodecFlags::R_CONT | CodecFlags::CONT_TYPE);
+                }
+                (FragmentKind::Continuation, ExtOpCode::Continuation)
+            }
+            DataCode::Text | DataCode::Binary if flags.contains(CodecFlags::R_CONT) => {
+                return Err(ProtocolError::ContinuationAlreadyStarted.into());
+            }
+            DataCode::Text => {
+                if !fin {
+                    flags.insert(CodecFlags::R_CONT | CodecFlags::CONT_TYPE);
+                }
+                (FragmentKind::Text, ExtOpCode::Text)
+            }
+            DataCode::Binary => {
+                if !fin {
+                    flags.insert(CodecFlags::R_CONT);
+                }
+                (FragmentKind::Binary, ExtOpCode::Binary)
+            }
+        };
+        if kind != FragmentKind::Continuation {
+            // Compressed messages are rejected above.
+            self.compressed = false;
+        }
+
+        extension_decode(&mut payload, extension, &header.flags, opcode)?;
+        Ok(FragmentItem::Data(kind, payload, fin))
     }
 }
 
-pub struct FramedWrite {
-    write_buffer: BytesMut,
-    rand: SmallRng,
+fn invalid_utf8() -> Error {
+    Error::with_cause(ErrorKind::Encoding, ProtocolError::Encoding)
 }
 
-impl Default for FramedWrite {
-    fn default() -> Self {
-        FramedWrite {
-            write_buffer: Default::default(),
-            rand: SmallRng::from_entropy(),
+fn decode_control(code: ControlCode, payload: BytesMut) -> Result<Item, Error> {
+    match code {
+        ControlCode::Close => {
+            let reason = if payload.len() < 2 {
+                None
+            } else {
+                match CloseCode::try_from([payload[0], payload[1]])? {
+                    close_code if close_code.is_illegal() => {
+                        return Err(ProtocolError::CloseCode(u16::from(close_code)).into())
+                    }
+                    close_code => {
+                        let close_reason = std::str::from_utf8(&payload[2..])
+                            .map_err(|_| invalid_utf8())?
+                            .to_string();
+                        let description = if close_reason.is_empty() {
+                            None
+                        } else {
+                            Some(close_reason)
+                        };
+
+                        let reason = CloseReason::new(close_code, description);
+
+                        Some(reason)
+                    }
+                }
+            };
+
+            Ok(Item::Close(reason))
         }
+        // The length of control frames is validated when their header is decoded.
+        ControlCode::Ping => Ok(Item::Ping(payload)),
+        ControlCode::Pong => Ok(Item::Pong(payload)),
     }
 }
 
+pub struct FramedWrite {
+    write_buffer: BytesMut,
+    key_gen: Box<dyn MaskingKeyGen>,
+    counts: Arc<ByteCounts>,
+    pool: Option<BufferPool>,
+    max_frame_size: Option<usize>,
+    write_timeout: Option<Duration>,
+    // Whether a write has timed out. The frame that was being written may have been partially
+    // written and so no further frames may be written.
+    failed: bool,
+}
+
 impl Debug for FramedWrite {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("FramedWrite")
             .field("write_buffer", &self.write_buffer)
+            .field("pool", &self.pool)
+            .field("max_frame_size", &self.max_frame_size)
+            .field("write_timeout", &self.write_timeout)
+            .field("failed", &self.failed)
             .finish()
     }
 }
 
 impl FramedWrite {
+    pub fn new(counts: Arc<ByteCounts>) -> FramedWrite {
+        FramedWrite {
+            write_buffer: Default::default(),
+            key_gen: Box::<EntropyKeyGen>::default(),
+            counts,
+            pool: None,
+            max_frame_size: None,
+            write_timeout: None,
+            failed: false,
+        }
+    }
+
+    pub fn set_write_timeout(&mut self, write_timeout: Option<Duration>) {
+        self.write_timeout = write_timeout;
+    }
+
+    pub fn set_buffer_pool(&mut self, pool: Option<BufferPool>) {
+        self.pool = pool;
+    }
+
+    pub fn set_max_frame_size(&mut self, max_frame_size: Option<usize>) {
+        self.max_frame_size = max_frame_size.filter(|size| *size > 0);
+    }
+
+    /// Returns the size that a data message of `len` bytes should be fragmented by, if it exceeds
+    /// the maximum outgoing frame size.
+    pub fn fragment_size(&self, len: usize) -> Option<usize> {
+        self.max_frame_size.filter(|size| len > *size)
+    }
+
+    pub fn counts(&self) -> &Arc<ByteCounts> {
+        &self.counts
+    }
+
+    pub fn set_masking_key_gen<G>(&mut self, key_gen: G)
+    where
+        G: MaskingKeyGen,
+    {
+        self.key_gen = Box::new(key_gen);
+    }
+
     pub async fn write<I, A, F>(
         &mut self,
         io: &mut I,
         is_server: bool,
         opcode: OpCode,
-        mut header_flags: HeaderFlags,
+        header_flags: HeaderFlags,
         payload_ref: A,
         extension: F,
     ) -> Result<(), Error>
@@ -364,11 +659,52 @@ impl FramedWrite {
         A: AsRef<[u8]>,
         F: FnMut(&mut BytesMut, &mut ExtFrameHeader) -> Result<(), Error>,
     {
-        let FramedWrite { write_buffer, rand } = self;
-        let payload = payload_ref.as_ref();
+        self.write_slices(
+            io,
+            is_server,
+            opcode,
+            header_flags,
+            &[payload_ref.as_ref()],
+            extension,
+        )
+        .await
+    }
 
-        let mut payload_bytes = BytesMut::with_capacity(payload.len());
-        payload_bytes.extend_from_slice(payload);
+    /// Writes a frame with a payload of the concatenation of `slices`.
+    pub async fn write_slices<I, F>(
+        &mut self,
+        io: &mut I,
+        is_server: bool,
+        opcode: OpCode,
+        mut header_flags: HeaderFlags,
+        slices: &[&[u8]],
+        extension: F,
+    ) -> Result<(), Error>
+    where
+        I: AsyncWrite + Unpin,
+        F: FnMut(&mut BytesMut, &mut ExtFrameHeader) -> Result<(), Error>,
+    {
+        let FramedWrite {
+            write_buffer,
+            key_gen,
+            counts,
+            pool,
+            write_timeout,
+            failed,
+            ..
+        } = self;
+        if *failed {
+            return Err(Error::with_cause(ErrorKind::Close, CloseCause::Error));
+        }
+        let len = slices.iter().map(|slice| slice.len()).sum();
+
+        let mut payload_bytes = match pool {
+            Some(pool) => pool.take(len),
+            None => PooledBuffer::unpooled(len),
+        };
+        for slice in slices {
+            payload_bytes.extend_from_slice(slice);
+        }
 
         if let OpCode::DataCode(data_code) = opcode {
             extension_encode(
@@ -382,14 +718,14 @@ impl FramedWrite {
         let mask = if is_server {
             None
         } else {
-            let mask = rand.gen();
+            let mask = key_gen.next_key();
             apply_mask(mask, payload_bytes.as_mut());
             Some(mask)
         };
 
         trace!(
             "Writing frame: {}",
-            BorrowedFramePrinter::new(&opcode, &header_flags, &mask),
+            BorrowedFramePrinter::new(&opcode, &header_flags, &mask, payload_bytes.len()),
         );
 
         FrameHeader::write_into(
@@ -400,11 +736,209 @@ impl FramedWrite {
             payload_bytes.len(),
         );
 
-        io.write_all(write_buffer).await?;
+        let result = with_timeout(*write_timeout, async {
+            io.write_all(write_buffer).await?;
+            counts.add_written(write_buffer.len());
+
+            io.write_all(payload_bytes.as_ref()).await?;
+            counts.add_written(payload_bytes.len());
+            io.flush().await.map_err(Into::into)
+        })
+        .await;
+        write_buffer.clear();
+        if matches!(&result, Err(e) if e.is_timeout()) {
+            *failed = true;
+        }
+        result
+    }
+
+    /// Writes an unmasked frame without copying `payload`. The header and the payload are written
+    /// using a vectored write if the underlying IO supports it.
+    ///
+    /// As no extension is applied to the payload and no masking is performed, this must only be
+    /// used by a server which has not negotiated an extension.
+    pub async fn write_unmasked<I, B>(
+        &mut self,
+        io: &mut I,
+        opcode: OpCode,
+        header_flags: HeaderFlags,
+        payload: B,
+    ) -> Result<(), Error>
+    where
+        I: AsyncWrite + Unpin,
+        B: Buf,
+    {
+        let FramedWrite {
+            write_buffer,
+            counts,
+            write_timeout,
+            failed,
+            ..
+        } = self;
+        if *failed {
+            return Err(Error::with_cause(ErrorKind::Close, CloseCause::Error));
+        }
+
+        trace!(
+            "Writing frame: {}",
+            BorrowedFramePrinter::new(&opcode, &header_flags, &None, payload.remaining()),
+        );
+
+        FrameHeader::write_into(
+            write_buffer,
+            opcode,
+            header_flags,
+            None,
+            payload.remaining(),
+        );
+
+        let len = write_buffer.len() + payload.remaining();
+        let result = with_timeout(*write_timeout, async {
+            let mut frame = (&mut *write_buffer).chain(payload);
+            io.write_all_buf(&mut frame).await?;
+            counts.add_written(len);
+
+            io.flush().await.map_err(Into::into)
+        })
+        .await;
         write_buffer.clear();
+        if matches!(&result, Err(e) if e.is_timeout()) {
+            *failed = true;
+        }
+        result
+    }
+}
 
-        io.write_all(payload_bytes.as_ref()).await?;
-        io.flush().await.map_err(Into::into)
+/// A `Buf` over the concatenation of a list of slices, which are written using a vectored write if
+/// the underlying IO supports it.
+#[cfg(feature = "split")]
+pub struct Slices<'a> {
+    slices: &'a [&'a [u8]],
+    // The number of bytes of the first slice that have been consumed.
+    offset: usize,
+}
+
+#[cfg(feature = "split")]
+impl<'a> Slices<'a> {
+    pub fn new(slices: &'a [&'a [u8]]) -> Slices<'a> {
+        Slices { slices, offset: 0 }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        self.slices
+            .iter()
+            .enumerate()
+            .map(move |(idx, slice)| {
+                if idx == 0 {
+                    &slice[self.offset..]
+                } else {
+                    slice
+                }
+            })
+            .filter(|slice| !slice.is_empty())
+    }
+}
+
+#[cfg(feature = "split")]
+impl Buf for Slices<'_> {
+    fn remaining(&self) -> usize {
+        self.iter().map(<[u8]>::len).sum()
+    }
+
+    fn chunk(&self) -> &[u8] {
+        self.iter().next().unwrap_or_default()
+    }
+
+    fn chunks_vectored<'b>(&'b self, dst: &mut [IoSlice<'b>]) -> usize {
+        dst.iter_mut()
+            .zip(self.iter())
+            .map(|(dst, slice)| *dst = IoSlice::new(slice))
+            .count()
+    }
+
+    fn advance(&mut self, mut cnt: usize) {
+        while let Some(first) = self.slices.first() {
+            let remaining = first.len() - self.offset;
+            if cnt < remaining {
+                self.offset += cnt;
+                return;
+            }
+            cnt -= remaining;
+            self.slices = &self.slices[1..];
+            self.offset = 0;
+        }
+    }
+}
+
+/// Validates the type of the first data message that is received by a server.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FirstMessage {
+    expected: Option<MessageType>,
+    close: bool,
+}
+
+impl FirstMessage {
+    pub fn new(role: Role, expected: Option<MessageType>, close: bool) -> FirstMessage {
+        match role {
+            Role::Client => FirstMessage::default(),
+            Role::Server => FirstMessage { expected, close },
+        }
+    }
+
+    /// Checks `received` against the expected message type. Only the first invocation performs a
+    /// check.
+    pub fn check(&mut self, received: MessageType) -> Result<(), Error> {
+        match self.expected.take() {
+            Some(expected) if expected != received => Err(Error::with_cause(
+                ErrorKind::Protocol,
+                ProtocolError::UnexpectedOpCode,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the reason to close the connection with if the check failed.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        if self.close {
+            Some(CloseReason::new(CloseCode::Policy, None))
+        } else {
+            None
+        }
+    }
+}
+
+/// Limits the rate at which pongs are automatically sent in response to pings.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PongLimit {
+    interval: Option<Duration>,
+    last: Option<Instant>,
+}
+
+impl PongLimit {
+    pub fn new(interval: Option<Duration>) -> PongLimit {
+        PongLimit {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Returns whether a pong may be sent now. If it may, then the time is recorded as that of the
+    /// last pong.
+    pub fn permit(&mut self) -> bool {
+        let PongLimit { interval, last } = self;
+        match interval {
+            Some(interval) => {
+                let now = Instant::now();
+                match last {
+                    Some(last) if now.duration_since(*last) < *interval => false,
+                    _ => {
+                        *last = Some(now);
+                        true
+                    }
+                }
+            }
+            None => true,
+        }
     }
 }
 
@@ -415,6 +949,9 @@ pub struct FramedIoParts<I> {
     pub writer: FramedWrite,
     pub flags: CodecFlags,
     pub max_message_size: usize,
+    pub first_message: FirstMessage,
+    pub auto_pong: bool,
+    pub pong_limit: PongLimit,
 }
 
 #[derive(Debug)]
@@ -424,6 +961,9 @@ pub struct FramedIo<I> {
     writer: FramedWrite,
     flags: CodecFlags,
     max_message_size: usize,
+    first_message: FirstMessage,
+    auto_pong: bool,
+    pong_limit: PongLimit,
 }
 
 impl<I> FramedIo<I>
@@ -438,6 +978,9 @@ where
             writer,
             flags,
             max_message_size,
+            first_message,
+            auto_pong,
+            pong_limit,
         } = parts;
         FramedIo {
             io,
@@ -445,6 +988,9 @@ where
             writer,
             flags,
             max_message_size,
+            first_message,
+            auto_pong,
+            pong_limit,
         }
     }
 
@@ -456,6 +1002,9 @@ where
             writer,
             flags,
             max_message_size,
+            first_message,
+            auto_pong,
+            pong_limit,
         } = self;
         FramedIoParts {
             io,
@@ -463,6 +1012,9 @@ where
             writer,
             flags,
             max_message_size,
+            first_message,
+            auto_pong,
+            pong_limit,
         }
     }
 
@@ -478,12 +1030,17 @@ where
             Role::Server => CodecFlags::from_bits_truncate(CodecFlags::ROLE.bits() | ext_bits),
         };
 
+        let counts = Arc::<ByteCounts>::default();
+
         FramedIo {
             io,
-            reader: FramedRead::new(read_buffer),
-            writer: FramedWrite::default(),
+            reader: FramedRead::new(read_buffer, counts.clone()),
+            writer: FramedWrite::new(counts),
             flags,
             max_message_size,
+            first_message: FirstMessage::default(),
+            auto_pong: true,
+            pong_limit: PongLimit::default(),
         }
     }
 
@@ -491,6 +1048,79 @@ where
         self.flags.contains(CodecFlags::ROLE)
     }
 
+    pub fn io(&self) -> &I {
+        &self.io
+    }
+
+    pub fn byte_counts(&self) -> (u64, u64) {
+        self.writer.counts().get()
+    }
+
+    pub fn set_masking_key_gen<G>(&mut self, key_gen: G)
+    where
+        G: MaskingKeyGen,
+    {
+        self.writer.set_masking_key_gen(key_gen);
+    }
+
+    pub fn set_first_message(&mut self, first_message: FirstMessage) {
+        self.first_message = first_message;
+    }
+
+    pub fn first_message(&mut self) -> &mut FirstMessage {
+        &mut self.first_message
+    }
+
+    pub fn set_accept_unmasked(&mut self, accept_unmasked: bool) {
+        self.flags.set(CodecFlags::ACCEPT_UNMASKED, accept_unmasked);
+    }
+
+    pub fn set_validate_utf8(&mut self, validate_utf8: bool) {
+        self.flags.set(CodecFlags::VALIDATE_UTF8, validate_utf8);
+    }
+
+    pub fn set_auto_pong(&mut self, auto_pong: bool) {
+        self.auto_pong = auto_pong;
+    }
+
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    pub fn set_buffer_pool(&mut self, pool: Option<BufferPool>) {
+        self.writer.set_buffer_pool(pool);
+    }
+
+    pub fn last_message_compressed(&self) -> bool {
+        self.reader.last_message_compressed()
+    }
+
+    pub fn set_max_outgoing_frame_size(&mut self, max_frame_size: Option<usize>) {
+        self.writer.set_max_frame_size(max_frame_size);
+    }
+
+    pub fn set_max_fragments(&mut self, max_fragments: Option<usize>) {
+        self.reader.set_max_fragments(max_fragments);
+    }
+
+    pub fn set_write_timeout(&mut self, write_timeout: Option<Duration>) {
+        self.writer.set_write_timeout(write_timeout);
+    }
+
+    pub fn fragment_size(&self, len: usize) -> Option<usize> {
+        self.writer.fragment_size(len)
+    }
+
+    pub fn set_pong_limit(&mut self, pong_limit: PongLimit) {
+        self.pong_limit = pong_limit;
+    }
+
+    /// Returns whether a pong should be automatically sent in response to a ping that has been
+    /// received.
+    pub fn should_pong(&mut self) -> bool {
+        self.auto_pong && self.pong_limit.permit()
+    }
+
     pub async fn write<A, F>(
         &mut self,
         opcode: OpCode,
@@ -517,6 +1147,18 @@ where
             .await
     }
 
+    pub async fn write_unmasked(
+        &mut self,
+        opcode: OpCode,
+        header_flags: HeaderFlags,
+        payload: Bytes,
+    ) -> Result<(), Error> {
+        let FramedIo { io, writer, .. } = self;
+        writer
+            .write_unmasked(io, opcode, header_flags, payload)
+            .await
+    }
+
     pub(crate) async fn read_next<E>(
         &mut self,
         read_into: &mut BytesMut,
@@ -590,13 +1232,36 @@ where
 
     let props = ReadProps {
         is_server,
+        accept_unmasked: flags.contains(CodecFlags::ACCEPT_UNMASKED),
         rsv_bits,
-        max_message_size,
+        max_message_size: message_limit(max_message_size),
     };
 
     reader.read(io, flags, read_into, extension, props).await
 }
 
+#[cfg(feature = "split")]
+pub async fn read_next_fragment<I, E>(
+    io: &mut I,
+    reader: &mut FramedRead,
+    flags: &mut CodecFlags,
+    max_message_size: usize,
+    extension: &mut E,
+) -> Result<FragmentItem, Error>
+where
+    I: AsyncRead + Unpin,
+    E: ExtensionDecoder,
+{
+    let props = ReadProps {
+        is_server: flags.contains(CodecFlags::ROLE),
+        accept_unmasked: flags.contains(CodecFlags::ACCEPT_UNMASKED),
+        rsv_bits: flags.bits() & 0x70,
+        max_message_size: message_limit(max_message_size),
+    };
+
+    reader.read_fragment(io, flags, extension, props).await
+}
+
 pub async fn write_close<I>(
     io: &mut I,
     writer: &mut FramedWrite,
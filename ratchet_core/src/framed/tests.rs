@@ -15,13 +15,13 @@
 use crate::errors::{Error, ProtocolError};
 use crate::ext::NoExt;
 use crate::framed::{CodecFlags, FramedIo, Item};
-use crate::protocol::{CloseCode, CloseCodeParseErr, CloseReason, DataCode, OpCode};
-use crate::protocol::{HeaderFlags, Role};
+use crate::protocol::{BorrowedFramePrinter, FrameHeader, FramePrinter, HeaderFlags, Role};
+use crate::protocol::{CloseCode, CloseReason, ControlCode, DataCode, OpCode, OpCodeParseErr};
 use crate::test_fixture::{expect_err, EmptyIo, MirroredIo};
 use bytes::BytesMut;
-use std::error::Error as StdError;
 use std::fmt::Debug;
 use std::iter::FromIterator;
+use tokio::io::{duplex, AsyncWriteExt};
 
 #[tokio::test]
 async fn frame_text() {
@@ -209,6 +209,31 @@ async fn ping() {
     );
 }
 
+#[test]
+fn frame_trace() {
+    let header = FrameHeader {
+        opcode: OpCode::ControlCode(ControlCode::Ping),
+        flags: HeaderFlags::FIN,
+        mask: None,
+    };
+    let expected = "opcode: Ping, fin: true, rsv: 0b000, masked: false, len: 5";
+    assert_eq!(FramePrinter(&header, 5).to_string(), expected);
+    assert_eq!(
+        BorrowedFramePrinter::new(&header.opcode, &header.flags, &header.mask, 5).to_string(),
+        expected
+    );
+
+    let header = FrameHeader {
+        opcode: OpCode::DataCode(DataCode::Binary),
+        flags: HeaderFlags::RSV_1,
+        mask: Some(1),
+    };
+    assert_eq!(
+        FramePrinter(&header, 128).to_string(),
+        "opcode: Binary, fin: false, rsv: 0b100, masked: true, len: 128"
+    );
+}
+
 #[tokio::test]
 async fn pong() {
     let buffer = BytesMut::from_iter(&[138, 4, 1, 2, 3, 4]);
@@ -249,6 +274,14 @@ async fn close() {
     )
     .await;
 
+    let buffer = BytesMut::from_iter(vec![136, 2, 0, 0]);
+    let mut framed = FramedIo::new(EmptyIo, buffer, Role::Client, usize::MAX, 0);
+
+    let decode_result = framed.read_next(&mut BytesMut::default(), &mut NoExt).await;
+    let error = decode_result.unwrap_err();
+    assert!(error.is_protocol());
+    assert_eq!(error.protocol_error(), Some(&ProtocolError::CloseCode(0)));
+
     let mut frame = vec![136, 126, 1, 0];
     frame.extend_from_slice(&[0; 256]);
 
@@ -256,9 +289,218 @@ async fn close() {
     let mut framed = FramedIo::new(EmptyIo, buffer, Role::Client, usize::MAX, 0);
 
     let decode_result = framed.read_next(&mut BytesMut::default(), &mut NoExt).await;
-    let error = decode_result.unwrap_err();
     assert_eq!(
-        error.source().unwrap().to_string(),
-        CloseCodeParseErr(0).to_string()
+        decode_result.unwrap_err().protocol_error(),
+        Some(&ProtocolError::ControlFrameOverflow)
     );
 }
+
+#[tokio::test]
+async fn client_rejects_masked_frame() {
+    let buffer = BytesMut::from_iter(&[129, 133, 55, 250, 33, 61, 127, 159, 77, 81, 88]);
+    let mut framed = FramedIo::new(EmptyIo, buffer, Role::Client, usize::MAX, 0);
+
+    let result = framed.read_next(&mut BytesMut::default(), &mut NoExt).await;
+    let error = result.as_ref().unwrap_err();
+    assert!(error.is_protocol());
+    expect_err(result, ProtocolError::MaskedFrame);
+}
+
+#[tokio::test]
+async fn server_rejects_unmasked_frame() {
+    let buffer = BytesMut::from_iter(&[129, 5, 72, 101, 108, 108, 111]);
+    let mut framed = FramedIo::new(EmptyIo, buffer, Role::Server, usize::MAX, 0);
+
+    let result = framed.read_next(&mut BytesMut::default(), &mut NoExt).await;
+    let error = result.as_ref().unwrap_err();
+    assert!(error.is_protocol());
+    expect_err(result, ProtocolError::UnmaskedFrame);
+}
+
+#[tokio::test]
+async fn desync() {
+    // A reserved OpCode with a 64-bit payload length that has its most significant bit set
+    let buffer = BytesMut::from_iter(&[131, 127, 255, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    let mut framed = FramedIo::new(EmptyIo, buffer, Role::Client, usize::MAX, 0);
+
+    let error = framed
+        .read_next(&mut BytesMut::default(), &mut NoExt)
+        .await
+        .unwrap_err();
+    assert!(error.is_desync());
+
+    // A reserved OpCode with a non-minimal 16-bit payload length
+    let buffer = BytesMut::from_iter(&[139, 126, 0, 4, 1, 2, 3, 4]);
+    let mut framed = FramedIo::new(EmptyIo, buffer, Role::Client, usize::MAX, 0);
+
+    let error = framed
+        .read_next(&mut BytesMut::default(), &mut NoExt)
+        .await
+        .unwrap_err();
+    assert!(error.is_desync());
+
+    // A reserved OpCode alone is not considered to be a desync
+    let buffer = BytesMut::from_iter(&[131, 4, 1, 2, 3, 4]);
+    let mut framed = FramedIo::new(EmptyIo, buffer, Role::Client, usize::MAX, 0);
+
+    let error = framed
+        .read_next(&mut BytesMut::default(), &mut NoExt)
+        .await
+        .unwrap_err();
+    assert!(error.is_protocol());
+    assert!(!error.is_desync());
+}
+
+#[tokio::test]
+async fn desync_byte_at_a_time() {
+    async fn read_segmented(bytes: &'static [u8]) -> Error {
+        // A maximum buffer size of one byte ensures that each read yields a single byte
+        let (tx, mut rx) = duplex(1);
+        let mut framed = FramedIo::new(tx, BytesMut::new(), Role::Client, usize::MAX, 0);
+
+        // The write fails once the frame has been rejected and the reader has been dropped
+        tokio::spawn(async move { rx.write_all(bytes).await });
+
+        framed
+            .read_next(&mut BytesMut::default(), &mut NoExt)
+            .await
+            .unwrap_err()
+    }
+
+    let error = read_segmented(&[131, 127, 255, 1, 2, 3, 4, 5, 6, 7, 8, 9]).await;
+    assert!(error.is_desync());
+
+    let error = read_segmented(&[139, 126, 0, 4, 1, 2, 3, 4]).await;
+    assert!(error.is_desync());
+
+    let error = read_segmented(&[131, 4, 1, 2, 3, 4]).await;
+    assert!(error.is_protocol());
+    assert!(!error.is_desync());
+}
+
+#[tokio::test]
+async fn rejects_reserved_opcodes() {
+    for opcode in [0x3, 0xB] {
+        let buffer = BytesMut::from_iter(&[0x80 | opcode, 0x80, 1, 2, 3, 4]);
+        let mut framed = FramedIo::new(EmptyIo, buffer, Role::Server, usize::MAX, 0);
+
+        let result = framed.read_next(&mut BytesMut::default(), &mut NoExt).await;
+        let error = result.as_ref().unwrap_err();
+        assert!(error.is_protocol());
+        expect_err(
+            result,
+            ProtocolError::OpCode(OpCodeParseErr::Reserved(opcode)),
+        );
+    }
+}
+
+#[tokio::test]
+async fn protocol_error_variants() {
+    async fn test(frame: &[u8], role: Role, validate_utf8: bool, expected: ProtocolError) {
+        let buffer = BytesMut::from_iter(frame);
+        let mut framed = FramedIo::new(EmptyIo, buffer, role, usize::MAX, 0);
+        framed.set_validate_utf8(validate_utf8);
+
+        let error = framed
+            .read_next(&mut BytesMut::default(), &mut NoExt)
+            .await
+            .unwrap_err();
+        assert_eq!(error.protocol_error(), Some(&expected));
+    }
+
+    // A reserved OpCode
+    test(
+        &[0x83, 0x80, 0, 0, 0, 0],
+        Role::Server,
+        false,
+        ProtocolError::OpCode(OpCodeParseErr::Reserved(0x3)),
+    )
+    .await;
+    // RSV1 set without an extension having been negotiated
+    test(
+        &[0xC1, 0x80, 0, 0, 0, 0],
+        Role::Server,
+        false,
+        ProtocolError::UnknownExtension,
+    )
+    .await;
+    // Invalid UTF-8 in a text message
+    test(
+        &[0x81, 0x81, 0, 0, 0, 0, 0xFF],
+        Role::Server,
+        true,
+        ProtocolError::Encoding,
+    )
+    .await;
+    // Invalid UTF-8 in a close reason
+    test(
+        &[0x88, 0x03, 0x03, 0xE8, 0xFF],
+        Role::Client,
+        false,
+        ProtocolError::Encoding,
+    )
+    .await;
+    // A ping with a payload that is longer than 125 bytes
+    let mut frame = vec![0x89, 126, 0, 126];
+    frame.extend_from_slice(&[0; 126]);
+    test(
+        &frame,
+        Role::Client,
+        false,
+        ProtocolError::ControlFrameOverflow,
+    )
+    .await;
+    // A fragmented control frame
+    test(
+        &[0x09, 0x00],
+        Role::Client,
+        false,
+        ProtocolError::FragmentedControl,
+    )
+    .await;
+    // Masking violations
+    test(
+        &[0x82, 0x80, 0, 0, 0, 0],
+        Role::Client,
+        false,
+        ProtocolError::MaskedFrame,
+    )
+    .await;
+    test(
+        &[0x82, 0x00],
+        Role::Server,
+        false,
+        ProtocolError::UnmaskedFrame,
+    )
+    .await;
+    // A close code that may not be sent by a peer
+    test(
+        &[0x88, 0x02, 0x03, 0xED],
+        Role::Client,
+        false,
+        ProtocolError::CloseCode(1005),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn unexpected_continuation() {
+    // A final continuation frame with no message in progress.
+    let buffer = BytesMut::from_iter(&[0x80, 0x82, 0, 0, 0, 0, 1, 2]);
+    let mut framed = FramedIo::new(EmptyIo, buffer, Role::Server, usize::MAX, 0);
+
+    let result = framed.read_next(&mut BytesMut::default(), &mut NoExt).await;
+    assert!(result.as_ref().unwrap_err().is_protocol());
+    expect_err(result, ProtocolError::ContinuationNotStarted);
+}
+
+#[tokio::test]
+async fn new_message_while_fragmented() {
+    // A binary frame without FIN followed by a text frame before the binary message is complete.
+    let buffer = BytesMut::from_iter(&[0x02, 0x82, 0, 0, 0, 0, 1, 2, 0x81, 0x82, 0, 0, 0, 0, 3, 4]);
+    let mut framed = FramedIo::new(EmptyIo, buffer, Role::Server, usize::MAX, 0);
+
+    let result = framed.read_next(&mut BytesMut::default(), &mut NoExt).await;
+    assert!(result.as_ref().unwrap_err().is_protocol());
+    expect_err(result, ProtocolError::ContinuationAlreadyStarted);
+}
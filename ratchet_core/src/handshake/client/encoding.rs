@@ -26,7 +26,12 @@ use crate::handshake::{
     apply_to, ProtocolRegistry, UPGRADE_STR, WEBSOCKET_STR, WEBSOCKET_VERSION_STR,
 };
 
-pub fn encode_request(dst: &mut BytesMut, request: ValidatedRequest, nonce_buffer: &mut Nonce) {
+pub fn encode_request(
+    dst: &mut BytesMut,
+    request: ValidatedRequest,
+    nonce_buffer: &mut Nonce,
+    key: Option<[u8; 16]>,
+) {
     let ValidatedRequest {
         version,
         headers,
@@ -34,7 +39,7 @@ pub fn encode_request(dst: &mut BytesMut, request: ValidatedRequest, nonce_buffe
         host,
     } = request;
 
-    let nonce = rand::random::<[u8; 16]>();
+    let nonce = key.unwrap_or_else(rand::random);
     encode_config_slice(nonce, base64::STANDARD, nonce_buffer);
     let nonce_str = std::str::from_utf8(nonce_buffer).expect("Invalid UTF8");
 
@@ -17,7 +17,7 @@ mod tests;
 
 mod encoding;
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use http::{header, Request, StatusCode};
 use httparse::{Response, Status};
 use log::{error, trace};
@@ -29,8 +29,9 @@ use crate::ext::NegotiatedExtension;
 use crate::handshake::client::encoding::{build_request, encode_request};
 use crate::handshake::io::BufferedIo;
 use crate::handshake::{
-    negotiate_response, validate_header, validate_header_value, ParseResult, ProtocolRegistry,
-    StreamingParser, ACCEPT_KEY, BAD_STATUS_CODE, UPGRADE_STR, WEBSOCKET_STR,
+    negotiate_response, validate_header, validate_header_value, with_timeout, HandshakeTranscript,
+    ParseResult, ProtocolRegistry, StreamingParser, ACCEPT_KEY, BAD_STATUS_CODE, UPGRADE_STR,
+    WEBSOCKET_STR,
 };
 use crate::{
     NoExt, NoExtProvider, Role, TryIntoRequest, WebSocket, WebSocketConfig, WebSocketStream,
@@ -51,6 +52,9 @@ pub struct UpgradedClient<S, E> {
     pub websocket: WebSocket<S, E>,
     /// An optional subprotocol that was negotiated during the upgrade.
     pub subprotocol: Option<String>,
+    /// The raw bytes exchanged during the handshake, if `WebSocketConfig::capture_handshake` was
+    /// set.
+    pub transcript: Option<HandshakeTranscript>,
 }
 
 impl<S, E> UpgradedClient<S, E> {
@@ -72,32 +76,60 @@ where
     R: TryIntoRequest,
 {
     let mut read_buffer = BytesMut::new();
-    let HandshakeResult {
-        subprotocol,
-        extension,
-    } = exec_client_handshake(
-        &mut stream,
-        request.try_into_request()?,
-        NoExtProvider,
-        ProtocolRegistry::default(),
-        &mut read_buffer,
+    let (
+        HandshakeResult {
+            subprotocol,
+            extension,
+        },
+        transcript,
+    ) = with_timeout(
+        config.handshake_timeout,
+        exec_client_handshake(
+            &mut stream,
+            request.try_into_request()?,
+            NoExtProvider,
+            ProtocolRegistry::default(),
+            &mut read_buffer,
+            config.capture_handshake,
+            None,
+        ),
     )
     .await?;
 
     Ok(UpgradedClient {
-        websocket: WebSocket::from_upgraded(config, stream, extension, read_buffer, Role::Client),
+        websocket: WebSocket::from_upgraded(config, stream, extension, read_buffer, Role::Client)
+            .with_subprotocol(subprotocol.clone()),
         subprotocol,
+        transcript,
     })
 }
 
 /// Execute a WebSocket client handshake on `stream`, attempting to negotiate the extension and a
 /// subprotocol.
 pub async fn subscribe_with<S, E, R>(
+    config: WebSocketConfig,
+    stream: S,
+    request: R,
+    extension: E,
+    subprotocols: ProtocolRegistry,
+) -> Result<UpgradedClient<S, E::Extension>, Error>
+where
+    S: WebSocketStream,
+    E: ExtensionProvider,
+    R: TryIntoRequest,
+{
+    subscribe_with_key(config, stream, request, extension, subprotocols, None).await
+}
+
+/// Execute a WebSocket client handshake on `stream` using `key` as the `Sec-WebSocket-Key` if one
+/// is provided, instead of a randomly generated key.
+pub(crate) async fn subscribe_with_key<S, E, R>(
     config: WebSocketConfig,
     mut stream: S,
     request: R,
     extension: E,
     subprotocols: ProtocolRegistry,
+    key: Option<[u8; 16]>,
 ) -> Result<UpgradedClient<S, E::Extension>, Error>
 where
     S: WebSocketStream,
@@ -105,21 +137,31 @@ where
     R: TryIntoRequest,
 {
     let mut read_buffer = BytesMut::new();
-    let HandshakeResult {
-        subprotocol,
-        extension,
-    } = exec_client_handshake(
-        &mut stream,
-        request.try_into_request()?,
-        extension,
-        subprotocols,
-        &mut read_buffer,
+    let (
+        HandshakeResult {
+            subprotocol,
+            extension,
+        },
+        transcript,
+    ) = with_timeout(
+        config.handshake_timeout,
+        exec_client_handshake(
+            &mut stream,
+            request.try_into_request()?,
+            extension,
+            subprotocols,
+            &mut read_buffer,
+            config.capture_handshake,
+            key,
+        ),
     )
     .await?;
 
     Ok(UpgradedClient {
-        websocket: WebSocket::from_upgraded(config, stream, extension, read_buffer, Role::Client),
+        websocket: WebSocket::from_upgraded(config, stream, extension, read_buffer, Role::Client)
+            .with_subprotocol(subprotocol.clone()),
         subprotocol,
+        transcript,
     })
 }
 
@@ -129,20 +171,28 @@ async fn exec_client_handshake<S, E>(
     extension: E,
     subprotocols: ProtocolRegistry,
     buf: &mut BytesMut,
-) -> Result<HandshakeResult<E::Extension>, Error>
+    capture: bool,
+    key: Option<[u8; 16]>,
+) -> Result<(HandshakeResult<E::Extension>, Option<HandshakeTranscript>), Error>
 where
     S: WebSocketStream,
     E: ExtensionProvider,
 {
-    let machine = ClientHandshake::new(stream, subprotocols, &extension, buf);
+    let mut machine = ClientHandshake::new(stream, subprotocols, &extension, buf);
+    if capture {
+        machine.transcript = Some(HandshakeTranscript::default());
+    }
+    machine.key = key;
     let uri = request.uri().to_string();
     let handshake_result = machine.exec(request).await;
     match &handshake_result {
-        Ok(HandshakeResult {
-            subprotocol,
-            extension,
-            ..
-        }) => {
+        Ok((
+            HandshakeResult {
+                subprotocol,
+                extension,
+            },
+            _,
+        )) => {
             trace!(
                 "{} for: {}. Selected subprotocol: {:?} and extension: {:?}",
                 MSG_HANDSHAKE_COMPLETED,
@@ -164,6 +214,8 @@ struct ClientHandshake<'s, S, E> {
     nonce: Nonce,
     subprotocols: ProtocolRegistry,
     extension: &'s E,
+    transcript: Option<HandshakeTranscript>,
+    key: Option<[u8; 16]>,
 }
 
 pub struct ResponseParser<'b, E> {
@@ -215,6 +267,8 @@ where
             nonce: [0; 24],
             subprotocols,
             extension,
+            transcript: None,
+            key: None,
         }
     }
 
@@ -224,10 +278,17 @@ where
             nonce,
             extension,
             subprotocols,
+            transcript,
+            key,
         } = self;
 
         let validated_request = build_request(request, extension, subprotocols)?;
-        encode_request(buffered.buffer, validated_request, nonce);
+        encode_request(buffered.buffer, validated_request, nonce, *key);
+
+        if let Some(transcript) = transcript {
+            transcript.sent = Bytes::copy_from_slice(buffered.buffer);
+        }
+
         Ok(())
     }
 
@@ -245,6 +306,8 @@ where
             nonce,
             subprotocols,
             extension,
+            transcript,
+            ..
         } = self;
 
         let parser = StreamingParser::new(
@@ -256,18 +319,25 @@ where
             },
         );
 
-        parser.parse().await
+        let (result, raw) = parser.parse(transcript.is_some()).await?;
+        if let (Some(transcript), Some(raw)) = (transcript, raw) {
+            transcript.received = raw;
+        }
+
+        Ok(result)
     }
 
     // This is split up on purpose so that the individual functions can be called in unit tests.
     pub async fn exec(
         mut self,
         request: Request<()>,
-    ) -> Result<HandshakeResult<E::Extension>, Error> {
+    ) -> Result<(HandshakeResult<E::Extension>, Option<HandshakeTranscript>), Error> {
         self.encode(request)?;
         self.write().await?;
         self.clear_buffer();
-        self.read().await
+        let result = self.read().await?;
+
+        Ok((result, self.transcript))
     }
 }
 
@@ -386,18 +456,30 @@ where
 
             let expected = base64::encode(digest.finalize());
             if expected.as_bytes() != actual {
-                Err(Error::with_cause(ErrorKind::Http, HttpError::KeyMismatch))
+                Err(Error::with_cause(
+                    ErrorKind::Http,
+                    HttpError::KeyMismatch {
+                        expected,
+                        received: String::from_utf8_lossy(actual).to_string(),
+                    },
+                ))
             } else {
                 Ok(())
             }
         },
     )?;
 
+    let subprotocol = negotiate_response(subprotocols, response)?;
+    let negotiated = extension
+        .negotiate_client(response.headers)
+        .and_then(|negotiated| match negotiated {
+            Some(negotiated) => extension.negotiate_response(negotiated),
+            None => Ok(None),
+        })
+        .map_err(|e| Error::with_cause(ErrorKind::Extension, e))?;
+
     Ok(HandshakeResult {
-        subprotocol: negotiate_response(subprotocols, response)?,
-        extension: extension
-            .negotiate_client(response.headers)
-            .map_err(|e| Error::with_cause(ErrorKind::Extension, e))?
-            .into(),
+        subprotocol,
+        extension: negotiated.into(),
     })
 }
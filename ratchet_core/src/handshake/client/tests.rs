@@ -14,10 +14,13 @@
 
 use crate::errors::{Error, HttpError};
 use crate::ext::NoExt;
-use crate::handshake::client::{ClientHandshake, HandshakeResult};
+use crate::handshake::client::{ClientHandshake, HandshakeResult, Nonce};
 use crate::handshake::{ProtocolRegistry, ACCEPT_KEY, UPGRADE_STR, WEBSOCKET_STR};
 use crate::test_fixture::mock;
-use crate::{ErrorKind, NoExtProvider, ProtocolError, TryIntoRequest};
+use crate::{
+    subscribe, ErrorKind, NoExtProvider, ProtocolError, TryIntoRequest, WebSocketClientBuilder,
+    WebSocketConfig,
+};
 use bytes::BytesMut;
 use futures::future::join;
 use futures::FutureExt;
@@ -31,7 +34,8 @@ use ratchet_ext::{
 use sha1::{Digest, Sha1};
 use std::convert::Infallible;
 use std::sync::Arc;
-use tokio::io::AsyncReadExt;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::Notify;
 
 const TEST_URL: &str = "ws://127.0.0.1:9001/test";
@@ -147,6 +151,13 @@ impl Trigger {
 }
 
 async fn expect_server_error(response: Response<()>, expected_error: HttpError) {
+    expect_server_error_with(response, |_, error| assert_eq!(error, &expected_error)).await
+}
+
+async fn expect_server_error_with<F>(response: Response<()>, f: F)
+where
+    F: FnOnce(&Nonce, &HttpError),
+{
     let (mut server, mut stream) = mock();
 
     let (client_tx, client_rx) = Trigger::new();
@@ -177,7 +188,7 @@ async fn expect_server_error(response: Response<()>, expected_error: HttpError)
             .err()
             .map(|e| {
                 let error = e.downcast_ref::<HttpError>().expect(ERR);
-                assert_eq!(error, &expected_error);
+                f(&machine.nonce, error);
             })
             .expect(ERR);
     };
@@ -219,7 +230,25 @@ async fn incorrect_sec_websocket_accept() {
         .body(())
         .unwrap();
 
-    expect_server_error(response, HttpError::KeyMismatch).await;
+    expect_server_error_with(response, |nonce, error| {
+        let mut digest = Sha1::new();
+        digest.update(nonce);
+        digest.update(ACCEPT_KEY);
+        let expected = base64::encode(digest.finalize());
+
+        assert_eq!(
+            error,
+            &HttpError::KeyMismatch {
+                expected: expected.clone(),
+                received: "🔥".to_string(),
+            }
+        );
+
+        let message = error.to_string();
+        assert!(message.contains(&expected));
+        assert!(message.contains("🔥"));
+    })
+    .await;
 }
 
 #[tokio::test]
@@ -686,3 +715,77 @@ async fn negotiates_no_extension() {
     )
     .await;
 }
+
+#[tokio::test(start_paused = true)]
+async fn handshake_timeout() {
+    let (mut peer, stream) = mock();
+    let config = WebSocketConfig {
+        handshake_timeout: Some(Duration::from_secs(5)),
+        ..Default::default()
+    };
+
+    let client = subscribe(config, stream, TEST_URL);
+    let server = async move {
+        let mut buf = BytesMut::with_capacity(1024);
+        peer.read_buf(&mut buf).await.unwrap();
+        // A response that stalls part way through its headers.
+        peer.write_all(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n")
+            .await
+            .unwrap();
+        peer
+    };
+
+    let (result, _peer) = join(client, server).await;
+    match result {
+        Ok(_) => panic!("Expected the handshake to time out"),
+        Err(e) => assert!(e.is_timeout()),
+    }
+}
+
+async fn fixed_key_handshake(accept: &str) -> (Result<(), Error>, BytesMut) {
+    let (mut peer, stream) = mock();
+
+    let client = WebSocketClientBuilder::default()
+        .fixed_key(*b"the sample nonce")
+        .subscribe(stream, TEST_URL)
+        .map(|result| result.map(|_| ()));
+    let server = async move {
+        let mut buf = BytesMut::with_capacity(1024);
+        peer.read_buf(&mut buf).await.unwrap();
+
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept
+        );
+        peer.write_all(response.as_bytes()).await.unwrap();
+        buf
+    };
+
+    join(client, server).await
+}
+
+#[tokio::test]
+async fn fixed_key() {
+    let (result, request) = fixed_key_handshake("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=").await;
+    assert!(result.is_ok());
+
+    let mut headers = [httparse::EMPTY_HEADER; 32];
+    let mut parsed = httparse::Request::new(&mut headers);
+    assert!(matches!(parsed.parse(&request), Ok(Status::Complete(_))));
+    assert_header(
+        parsed.headers,
+        header::SEC_WEBSOCKET_KEY.as_str(),
+        "dGhlIHNhbXBsZSBub25jZQ==",
+    );
+
+    // The accept value is still verified against the fixed key.
+    let (result, _) = fixed_key_handshake("dGhlIHNhbXBsZSBub25jZQ==").await;
+    let err = result.expect_err("Expected a key mismatch");
+    assert_eq!(
+        err.downcast_ref::<HttpError>(),
+        Some(&HttpError::KeyMismatch {
+            expected: "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string(),
+            received: "dGhlIHNhbXBsZSBub25jZQ==".to_string(),
+        })
+    );
+}
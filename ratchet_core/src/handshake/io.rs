@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use crate::errors::Error;
-use bytes::{Buf, BytesMut};
+use bytes::{Bytes, BytesMut};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 const RESIZE: usize = 8 * 1024;
@@ -54,8 +54,8 @@ impl<'s, S> BufferedIo<'s, S> {
         Ok(())
     }
 
-    pub fn advance(&mut self, count: usize) {
-        self.buffer.advance(count);
+    pub fn take(&mut self, count: usize) -> Bytes {
+        self.buffer.split_to(count).freeze()
     }
 
     pub fn clear(&mut self) {
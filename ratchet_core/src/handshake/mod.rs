@@ -17,6 +17,7 @@ mod tests;
 
 mod client;
 mod io;
+mod origin;
 mod server;
 mod subprotocols;
 
@@ -28,12 +29,16 @@ use bytes::Bytes;
 use http::header::HeaderName;
 use http::Uri;
 use http::{HeaderMap, HeaderValue};
+use std::future::Future;
 use std::str::FromStr;
+use std::time::Duration;
 use tokio::io::AsyncRead;
 use tokio_util::codec::Decoder;
 use url::Url;
 
-pub use client::{subscribe, subscribe_with, HandshakeResult, UpgradedClient};
+pub(crate) use client::subscribe_with_key;
+pub use client::{subscribe, subscribe_with, UpgradedClient};
+pub(crate) use origin::OriginAllowlist;
 pub use server::{accept, accept_with, UpgradedServer, WebSocketResponse, WebSocketUpgrader};
 pub use subprotocols::*;
 
@@ -58,7 +63,9 @@ where
         StreamingParser { io, parser }
     }
 
-    pub async fn parse(self) -> Result<O, Error> {
+    /// Reads from the underlying IO until a complete message has been parsed. If `capture` is set,
+    /// then the bytes that the message was parsed from are also returned.
+    pub async fn parse(self, capture: bool) -> Result<(O, Option<Bytes>), Error> {
         let StreamingParser { io, mut parser } = self;
 
         loop {
@@ -66,8 +73,8 @@ where
 
             match parser.decode(io.buffer) {
                 Ok(Some((out, count))) => {
-                    io.advance(count);
-                    return Ok(out);
+                    let raw = io.take(count);
+                    return Ok((out, capture.then_some(raw)));
                 }
                 Ok(None) => continue,
                 Err(e) => return Err(e),
@@ -76,6 +83,33 @@ where
     }
 }
 
+/// Runs `future` to completion, failing with a timeout error if it does not complete within
+/// `timeout`.
+pub(crate) async fn with_timeout<F, O>(timeout: Option<Duration>, future: F) -> Result<O, Error>
+where
+    F: Future<Output = Result<O, Error>>,
+{
+    match timeout {
+        Some(duration) => match tokio::time::timeout(duration, future).await {
+            Ok(result) => result,
+            Err(e) => Err(Error::with_cause(ErrorKind::Timeout, e)),
+        },
+        None => future.await,
+    }
+}
+
+/// The raw bytes that were exchanged with a peer during a handshake.
+///
+/// This is only captured if `WebSocketConfig::capture_handshake` is set.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HandshakeTranscript {
+    /// The bytes of the request or response that was written to the peer.
+    pub sent: Bytes,
+    /// The bytes of the request or response that was read from the peer. This does not include
+    /// any bytes that followed it.
+    pub received: Bytes,
+}
+
 pub enum ParseResult<O> {
     Complete(O, usize),
     Partial,
@@ -87,13 +121,13 @@ pub trait TryIntoRequest {
     fn try_into_request(self) -> Result<Request, Error>;
 }
 
-impl<'a> TryIntoRequest for &'a str {
+impl TryIntoRequest for &str {
     fn try_into_request(self) -> Result<Request, Error> {
         self.parse::<Uri>()?.try_into_request()
     }
 }
 
-impl<'a> TryIntoRequest for &'a String {
+impl TryIntoRequest for &String {
     fn try_into_request(self) -> Result<Request, Error> {
         self.as_str().try_into_request()
     }
@@ -105,7 +139,7 @@ impl TryIntoRequest for String {
     }
 }
 
-impl<'a> TryIntoRequest for &'a Uri {
+impl TryIntoRequest for &Uri {
     fn try_into_request(self) -> Result<Request, Error> {
         self.clone().try_into_request()
     }
@@ -117,7 +151,7 @@ impl TryIntoRequest for Uri {
     }
 }
 
-impl<'a> TryIntoRequest for &'a Url {
+impl TryIntoRequest for &Url {
     fn try_into_request(self) -> Result<Request, Error> {
         self.as_str().try_into_request()
     }
@@ -232,7 +266,7 @@ impl<'h> TryMap<HeaderMap> for &'h [httparse::Header<'h>] {
     }
 }
 
-impl<'l, 'h, 'buf: 'h> TryMap<Request> for &'l httparse::Request<'h, 'buf> {
+impl<'h, 'buf: 'h> TryMap<Request> for &httparse::Request<'h, 'buf> {
     type Error = HttpError;
 
     fn try_map(self) -> Result<Request, Self::Error> {
@@ -0,0 +1,88 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{Error, ErrorKind, HttpError, Request};
+use http::header::ORIGIN;
+use std::borrow::Cow;
+
+const WILDCARD: &str = "://*.";
+
+/// A list of origins that a server will accept upgrade requests from.
+///
+/// Entries are matched against the `Origin` header of a request, ignoring ASCII case. An entry may
+/// contain a wildcard in place of the leftmost host labels, such as `https://*.example.com`, which
+/// matches any subdomain of `example.com` using the same scheme and port but not `example.com`
+/// itself.
+#[derive(Debug, Clone)]
+pub(crate) struct OriginAllowlist {
+    origins: Vec<Cow<'static, str>>,
+}
+
+impl OriginAllowlist {
+    pub fn new<I>(origins: I) -> OriginAllowlist
+    where
+        I: IntoIterator,
+        I::Item: Into<Cow<'static, str>>,
+    {
+        OriginAllowlist {
+            origins: origins.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Validates the `Origin` header of `request` against this allowlist.
+    ///
+    /// Requests without an `Origin` header are allowed as the header is only sent by browsers,
+    /// which are the only clients that the check protects against.
+    pub fn validate(&self, request: &Request) -> Result<(), Error> {
+        match request.headers().get(ORIGIN) {
+            Some(value) => {
+                let origin = String::from_utf8_lossy(value.as_bytes());
+                if self.allows(&origin) {
+                    Ok(())
+                } else {
+                    Err(Error::with_cause(
+                        ErrorKind::Http,
+                        HttpError::DisallowedOrigin(origin.into_owned()),
+                    ))
+                }
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.origins
+            .iter()
+            .any(|allowed| origin_matches(allowed, origin))
+    }
+}
+
+fn origin_matches(allowed: &str, origin: &str) -> bool {
+    match allowed.split_once(WILDCARD) {
+        Some((scheme, suffix)) => {
+            let origin = origin.to_ascii_lowercase();
+            let prefix = format!("{}://", scheme.to_ascii_lowercase());
+            let suffix = format!(".{}", suffix.to_ascii_lowercase());
+
+            match origin
+                .strip_prefix(&prefix)
+                .and_then(|host| host.strip_suffix(&suffix))
+            {
+                Some(subdomain) => !subdomain.is_empty() && !subdomain.contains([':', '/']),
+                None => false,
+            }
+        }
+        None => allowed.eq_ignore_ascii_case(origin),
+    }
+}
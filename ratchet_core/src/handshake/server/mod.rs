@@ -19,7 +19,7 @@ mod tests;
 use crate::ext::{NegotiatedExtension, NoExt};
 use crate::handshake::io::BufferedIo;
 use crate::handshake::server::encoding::{write_response, RequestParser};
-use crate::handshake::{StreamingParser, ACCEPT_KEY};
+use crate::handshake::{with_timeout, HandshakeTranscript, StreamingParser, ACCEPT_KEY};
 use crate::handshake::{UPGRADE_STR, WEBSOCKET_STR};
 use crate::protocol::Role;
 use crate::{
@@ -50,6 +50,9 @@ pub struct UpgradedServer<S, E> {
     pub websocket: WebSocket<S, E>,
     /// An optional subprotocol that was negotiated during the upgrade.
     pub subprotocol: Option<String>,
+    /// The raw bytes exchanged during the handshake, if `WebSocketConfig::capture_handshake` was
+    /// set.
+    pub transcript: Option<HandshakeTranscript>,
 }
 
 impl<S, E> UpgradedServer<S, E> {
@@ -100,8 +103,9 @@ where
         },
     );
 
-    match parser.parse().await {
-        Ok(result) => {
+    let parse = parser.parse(config.capture_handshake);
+    match with_timeout(config.handshake_timeout, parse).await {
+        Ok((result, raw)) => {
             let HandshakeResult {
                 key,
                 subprotocol,
@@ -127,6 +131,10 @@ where
                 subprotocol,
                 extension_header,
                 config,
+                transcript: raw.map(|received| HandshakeTranscript {
+                    sent: Bytes::new(),
+                    received,
+                }),
             })
         }
         Err(e) => {
@@ -197,6 +205,7 @@ pub struct WebSocketUpgrader<S, E> {
     extension: NegotiatedExtension<E>,
     extension_header: Option<HeaderValue>,
     config: WebSocketConfig,
+    transcript: Option<HandshakeTranscript>,
 }
 
 impl<S, E> WebSocketUpgrader<S, E>
@@ -209,6 +218,11 @@ where
         self.subprotocol.as_ref()
     }
 
+    /// Sets the subprotocol that will be sent to the client when the connection is upgraded.
+    pub(crate) fn set_subprotocol(&mut self, subprotocol: Option<String>) {
+        self.subprotocol = subprotocol;
+    }
+
     /// The URI that the client has requested.
     pub fn uri(&self) -> &Uri {
         self.request.uri()
@@ -242,6 +256,7 @@ where
             extension,
             extension_header,
             config,
+            mut transcript,
         } = self;
 
         let mut digest = Sha1::new();
@@ -280,14 +295,20 @@ where
         )
         .await?;
 
+        if let Some(transcript) = &mut transcript {
+            transcript.sent = Bytes::copy_from_slice(&buf);
+        }
+
         buf.clear();
 
         trace!("{} from {}", UPGRADED_MSG, request.uri());
 
         Ok(UpgradedServer {
             request,
-            websocket: WebSocket::from_upgraded(config, stream, extension, buf, Role::Server),
+            websocket: WebSocket::from_upgraded(config, stream, extension, buf, Role::Server)
+                .with_subprotocol(subprotocol.clone()),
             subprotocol,
+            transcript,
         })
     }
 
@@ -26,6 +26,8 @@ use ratchet_ext::{
     Extension, ExtensionDecoder, ExtensionEncoder, ExtensionProvider, FrameHeader,
     ReunitableExtension, RsvBits, SplittableExtension,
 };
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
 
 impl From<ReadError<httparse::Error>> for Error {
     fn from(e: ReadError<httparse::Error>) -> Self {
@@ -348,3 +350,25 @@ async fn multiple_connection_headers() {
 
     assert_response_eq(response, expected);
 }
+
+#[tokio::test(start_paused = true)]
+async fn handshake_timeout() {
+    let (mut client, server) = mock();
+
+    // A request that stalls part way through its headers.
+    client
+        .write_all(b"GET /test HTTP/1.1\r\nHost: localtoast\r\nConnection: Up")
+        .await
+        .unwrap();
+
+    let config = WebSocketConfig {
+        handshake_timeout: Some(Duration::from_secs(5)),
+        ..Default::default()
+    };
+    let result = accept_with(server, config, NoExtProvider, ProtocolRegistry::default()).await;
+
+    match result {
+        Ok(_) => panic!("Expected the handshake to time out"),
+        Err(e) => assert!(e.is_timeout()),
+    }
+}
@@ -29,6 +29,10 @@ pub struct ProtocolRegistry {
 
 impl ProtocolRegistry {
     /// Construct a new protocol registry that will allow the provided items.
+    ///
+    /// # Errors
+    /// Errors if any of the items are not a valid [RFC7230](https://datatracker.ietf.org/doc/html/rfc7230#section-3.2.6)
+    /// token.
     pub fn new<I>(i: I) -> Result<ProtocolRegistry, Error>
     where
         I: IntoIterator,
@@ -36,8 +40,18 @@ impl ProtocolRegistry {
     {
         let registrants = i
             .into_iter()
-            .map(Into::into)
-            .collect::<FnvHashSet<Cow<'static, str>>>();
+            .map(|protocol| {
+                let protocol = protocol.into();
+                if is_token(&protocol) {
+                    Ok(protocol)
+                } else {
+                    Err(Error::with_cause(
+                        ErrorKind::Http,
+                        HttpError::InvalidSubprotocol(protocol.into_owned()),
+                    ))
+                }
+            })
+            .collect::<Result<FnvHashSet<Cow<'static, str>>, Error>>()?;
         let header_str = registrants
             .clone()
             .into_iter()
@@ -54,6 +68,16 @@ impl ProtocolRegistry {
     }
 }
 
+/// Returns whether `value` is a valid RFC7230 token.
+fn is_token(value: &str) -> bool {
+    const SEPARATORS: &[u8] = b"()<>@,;:\\\"/[]?={} \t";
+
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii() && !b.is_ascii_control() && !SEPARATORS.contains(&b))
+}
+
 enum Bias {
     Client,
     Server,
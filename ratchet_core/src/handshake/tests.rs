@@ -13,8 +13,18 @@
 // limitations under the License.
 
 use crate::handshake::{negotiate_request, ProtocolRegistry};
-use crate::ProtocolError;
-use http::header::SEC_WEBSOCKET_PROTOCOL;
+use crate::test_fixture::{mock, MockPeer};
+use crate::{
+    accept_with, subscribe, Error, HttpError, NoExt, NoExtProvider, ProtocolError, UpgradedClient,
+    UpgradedServer, WebSocketClientBuilder, WebSocketConfig, WebSocketServerBuilder,
+};
+use futures::future::join;
+use http::header::{ORIGIN, SEC_WEBSOCKET_PROTOCOL};
+use http::StatusCode;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
 
 #[test]
 fn selects_protocol_ok() {
@@ -104,3 +114,298 @@ fn no_match() {
     let registry = ProtocolRegistry::new(vec!["d"]).unwrap();
     assert_eq!(negotiate_request(&registry, &request), Ok(None));
 }
+
+#[tokio::test]
+async fn transcript() {
+    let (client, server) = mock();
+    let config = WebSocketConfig {
+        capture_handshake: true,
+        ..Default::default()
+    };
+
+    let client = subscribe(config, client, "ws://127.0.0.1:9001/test");
+    let server = async move {
+        let upgrader = accept_with(server, config, NoExtProvider, ProtocolRegistry::default())
+            .await
+            .unwrap();
+        upgrader.upgrade().await.unwrap()
+    };
+
+    let (client, server) = join(client, server).await;
+    let client = client
+        .unwrap()
+        .transcript
+        .expect("Missing client transcript");
+    let server = server.transcript.expect("Missing server transcript");
+
+    assert!(client.sent.starts_with(b"GET /test HTTP/1.1\r\n"));
+    assert!(client
+        .received
+        .starts_with(b"HTTP/1.1 101 Switching Protocols"));
+    assert!(client.received.ends_with(b"\r\n\r\n"));
+
+    assert_eq!(client.sent, server.received);
+    assert_eq!(client.received, server.sent);
+}
+
+#[tokio::test]
+async fn no_transcript() {
+    let (client, server) = mock();
+    let config = WebSocketConfig::default();
+
+    let client = subscribe(config, client, "ws://127.0.0.1:9001/test");
+    let server = async move {
+        let upgrader = accept_with(server, config, NoExtProvider, ProtocolRegistry::default())
+            .await
+            .unwrap();
+        upgrader.upgrade().await.unwrap()
+    };
+
+    let (client, server) = join(client, server).await;
+    assert!(client.unwrap().transcript.is_none());
+    assert!(server.transcript.is_none());
+}
+
+#[test]
+fn invalid_subprotocol() {
+    let err = WebSocketClientBuilder::default()
+        .subprotocols(["warp", "bad protocol"])
+        .expect_err("Expected an invalid subprotocol");
+    assert!(err.is_http());
+    assert_eq!(
+        err.downcast_ref::<HttpError>(),
+        Some(&HttpError::InvalidSubprotocol("bad protocol".to_string()))
+    );
+
+    for protocol in ["", "a,b", "a\tb", "a/b", "\u{7f}", "é"] {
+        assert!(ProtocolRegistry::new([protocol]).is_err());
+    }
+
+    assert!(WebSocketClientBuilder::default()
+        .subprotocols(["warp", "warps2.0", "graphql-ws"])
+        .is_ok());
+}
+
+async fn exec_origin(
+    origin: Option<&str>,
+) -> (
+    Result<UpgradedClient<MockPeer, NoExt>, Error>,
+    Result<UpgradedServer<MockPeer, NoExt>, Error>,
+) {
+    let (client, server) = mock();
+
+    let mut builder = WebSocketClientBuilder::default();
+    if let Some(origin) = origin {
+        builder = builder.origin(origin).unwrap();
+    }
+
+    let client = builder.subscribe(client, "ws://127.0.0.1:9001/test");
+    let server = WebSocketServerBuilder::default()
+        .allowed_origins(["https://swim.ai", "https://*.example.com"])
+        .accept(server);
+
+    join(client, server).await
+}
+
+#[tokio::test]
+async fn allowed_origin() {
+    for origin in [
+        "https://swim.ai",
+        "HTTPS://SWIM.AI",
+        "https://chat.example.com",
+        "https://a.b.example.com",
+    ] {
+        let (client, server) = exec_origin(Some(origin)).await;
+        assert!(client.is_ok(), "{}: {:?}", origin, client);
+        let server = server.unwrap();
+        assert_eq!(server.request.headers()[ORIGIN], origin);
+    }
+}
+
+#[tokio::test]
+async fn disallowed_origin() {
+    for origin in [
+        "http://swim.ai",
+        "https://swim.ai.evil.com",
+        "https://example.com",
+        "http://chat.example.com",
+        "https://chat.example.com:8080",
+        "https://evil.com/.example.com",
+    ] {
+        let (client, server) = exec_origin(Some(origin)).await;
+
+        let err = client.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<HttpError>(),
+            Some(&HttpError::Status(StatusCode::FORBIDDEN))
+        );
+
+        let err = server.unwrap_err();
+        assert!(err.is_http());
+        assert_eq!(
+            err.downcast_ref::<HttpError>(),
+            Some(&HttpError::DisallowedOrigin(origin.to_string()))
+        );
+    }
+}
+
+#[tokio::test]
+async fn missing_origin() {
+    let (client, server) = exec_origin(None).await;
+    assert!(client.is_ok());
+    assert!(server.unwrap().request.headers().get(ORIGIN).is_none());
+}
+
+#[tokio::test]
+async fn connect_with_resolver() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let listener_addr = listener.local_addr().unwrap();
+
+    let resolver = move |host: &str, port: u16| -> io::Result<Vec<SocketAddr>> {
+        assert_eq!(host, "chat.swim.test");
+        assert_eq!(port, 9001);
+        Ok(vec![listener_addr])
+    };
+
+    let client = WebSocketClientBuilder::default()
+        .resolver(resolver)
+        .connect("ws://chat.swim.test:9001/test");
+    let server = async {
+        let (stream, _) = listener.accept().await.unwrap();
+        WebSocketServerBuilder::default().accept(stream).await
+    };
+
+    let (client, server) = join(client, server).await;
+    let client = client.expect("Client handshake failed");
+    assert!(server.is_ok());
+    assert_eq!(client.websocket.peer_addr().unwrap(), listener_addr);
+}
+
+#[tokio::test]
+async fn connect_no_addresses() {
+    let err = WebSocketClientBuilder::default()
+        .resolver(|_: &str, _: u16| Ok(Vec::new()))
+        .connect("ws://chat.swim.test/test")
+        .await
+        .expect_err("Expected a connection failure");
+    assert!(err.is_io());
+}
+
+#[tokio::test]
+async fn connect_rejects_unsupported_schemes() {
+    for uri in [
+        "wss://chat.swim.test:443/test",
+        "https://chat.swim.test:8443/test",
+        "wss://chat.swim.test/test",
+    ] {
+        let err = WebSocketClientBuilder::default()
+            .resolver(|_: &str, _: u16| -> io::Result<Vec<SocketAddr>> {
+                panic!("Unexpected resolution")
+            })
+            .connect(uri)
+            .await
+            .expect_err("Expected an unsupported scheme");
+        assert!(err.is_http());
+        assert!(matches!(
+            err.downcast_ref::<HttpError>(),
+            Some(HttpError::MalformattedUri(Some(msg))) if msg.starts_with("Unsupported scheme")
+        ));
+    }
+}
+
+#[tokio::test]
+async fn on_handshake() {
+    type Captured = Arc<Mutex<Option<(Vec<u8>, Vec<u8>)>>>;
+
+    fn observer(captured: &Captured) -> impl Fn(&[u8], &[u8]) + Send + Sync + 'static {
+        let captured = captured.clone();
+        move |request, response| {
+            *captured.lock().unwrap() = Some((request.to_vec(), response.to_vec()));
+        }
+    }
+
+    let client_captured = Captured::default();
+    let server_captured = Captured::default();
+    let (client, server) = mock();
+
+    let client = WebSocketClientBuilder::default()
+        .on_handshake(observer(&client_captured))
+        .subscribe(client, "ws://127.0.0.1:9001/test");
+    let server = WebSocketServerBuilder::default()
+        .on_handshake(observer(&server_captured))
+        .accept(server);
+
+    let (client, server) = join(client, server).await;
+    // The transcript is only retained if it was requested in the config.
+    assert!(client.unwrap().transcript.is_none());
+    assert!(server.unwrap().transcript.is_none());
+
+    let (request, response) = client_captured.lock().unwrap().take().unwrap();
+    let request = String::from_utf8(request).unwrap();
+    let response = String::from_utf8(response).unwrap();
+    assert!(request.starts_with("GET /test HTTP/1.1\r\n"));
+    assert!(request.contains("Upgrade: websocket\r\n"));
+    assert!(response.starts_with("HTTP/1.1 101"));
+
+    let server_exchange = server_captured.lock().unwrap().take().unwrap();
+    assert_eq!(
+        server_exchange,
+        (request.into_bytes(), response.into_bytes())
+    );
+}
+
+#[tokio::test]
+async fn select_protocol() {
+    async fn handshake(
+        path: &str,
+    ) -> (
+        Result<UpgradedClient<MockPeer, NoExt>, Error>,
+        Result<UpgradedServer<MockPeer, NoExt>, Error>,
+    ) {
+        let (client, server) = mock();
+
+        let client = WebSocketClientBuilder::default()
+            .subprotocols(["chat", "feed"])
+            .unwrap()
+            .subscribe(client, format!("ws://127.0.0.1:9001{}", path));
+        let server = WebSocketServerBuilder::default()
+            .select_protocol(|offered, request| {
+                assert_eq!(offered.len(), 2);
+                match request.uri().path() {
+                    "/chat" => Some("chat".to_string()),
+                    "/feed" => Some("feed".to_string()),
+                    "/none" => None,
+                    _ => Some("unknown".to_string()),
+                }
+            })
+            .accept(server);
+
+        join(client, server).await
+    }
+
+    for (path, expected) in [
+        ("/chat", Some("chat")),
+        ("/feed", Some("feed")),
+        ("/none", None),
+    ] {
+        let (client, server) = handshake(path).await;
+        let (client, server) = (client.unwrap(), server.unwrap());
+        assert_eq!(client.subprotocol.as_deref(), expected);
+        assert_eq!(client.websocket.subprotocol(), expected);
+        assert_eq!(server.subprotocol.as_deref(), expected);
+        assert_eq!(server.websocket.subprotocol(), expected);
+    }
+
+    // The client did not offer the selected subprotocol.
+    let (client, server) = handshake("/other").await;
+    let err = server.expect_err("Expected the handshake to fail");
+    assert_eq!(
+        err.downcast_ref::<ProtocolError>(),
+        Some(&ProtocolError::UnknownProtocol)
+    );
+    let err = client.expect_err("Expected the upgrade to be rejected");
+    assert_eq!(
+        err.downcast_ref::<HttpError>(),
+        Some(&HttpError::Status(StatusCode::INTERNAL_SERVER_ERROR))
+    );
+}
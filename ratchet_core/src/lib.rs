@@ -36,14 +36,20 @@ mod errors;
 mod ext;
 mod framed;
 mod handshake;
+mod ping;
+mod pool;
 mod protocol;
+mod resolver;
 mod ws;
 
 /// Split WebSocket implementation.
 #[cfg(feature = "split")]
 mod split;
 #[cfg(feature = "split")]
-pub use split::{Receiver, ReuniteError, Sender};
+pub use split::{
+    ChannelBridge, InboundMessage, OutboundMessage, Receiver, ReceiverStream, ReuniteError, Sender,
+    WebSocketByteStream,
+};
 
 #[allow(missing_docs)]
 #[cfg(feature = "fixture")]
@@ -55,18 +61,44 @@ pub use builder::{WebSocketClientBuilder, WebSocketServerBuilder};
 pub use errors::*;
 pub use ext::{NegotiatedExtension, NoExt, NoExtDecoder, NoExtEncoder, NoExtProvider};
 pub use handshake::{
-    accept, accept_with, subscribe, subscribe_with, ProtocolRegistry, TryIntoRequest,
-    UpgradedClient, UpgradedServer, WebSocketResponse, WebSocketUpgrader,
+    accept, accept_with, subscribe, subscribe_with, HandshakeTranscript, ProtocolRegistry,
+    TryIntoRequest, UpgradedClient, UpgradedServer, WebSocketResponse, WebSocketUpgrader,
 };
+pub use ping::PingTracker;
+pub use pool::BufferPool;
 pub use protocol::{
-    CloseCode, CloseReason, Message, MessageType, PayloadType, Role, WebSocketConfig,
+    CloseCode, CloseReason, FragmentKind, FrameConstraints, MaskingKeyGen, Message, MessageType,
+    PayloadType, Role, WebSocketConfig,
 };
+pub use resolver::{DnsResolver, ResolveFuture, Resolver};
 pub use ws::{CloseState, WebSocket};
 
+use std::io;
+use std::net::SocketAddr;
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
 
 pub(crate) type Request = http::Request<()>;
 
 /// A stream representing a WebSocket connection.
 pub trait WebSocketStream: AsyncRead + AsyncWrite + Unpin + 'static {}
 impl<S> WebSocketStream for S where S: AsyncRead + AsyncWrite + Unpin + 'static {}
+
+/// A stream that is able to report the addresses of the endpoints of its connection.
+pub trait HasPeerAddr {
+    /// Returns the address of the remote peer.
+    fn peer_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Returns the local address of this stream.
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+}
+
+impl HasPeerAddr for TcpStream {
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        TcpStream::peer_addr(self)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        TcpStream::local_addr(self)
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[cfg(test)]
+mod tests;
+
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Tracks the payloads of ping frames that have been sent so that the pong frames received in
+/// response to them can be matched up and the round trip time measured.
+///
+/// A peer responds to a ping frame with a pong frame containing the same payload, so each ping
+/// that is recorded should have a unique payload, such as a sequence number or a timestamp.
+/// Unsolicited pong frames, and pong frames for pings that have already been acknowledged or
+/// have expired, are ignored.
+#[derive(Debug, Default)]
+pub struct PingTracker {
+    outstanding: VecDeque<(Bytes, Instant)>,
+}
+
+impl PingTracker {
+    /// Constructs a new tracker with no outstanding pings.
+    pub fn new() -> PingTracker {
+        PingTracker::default()
+    }
+
+    /// Records that a ping frame with `payload` has been sent.
+    pub fn record_ping<P>(&mut self, payload: P)
+    where
+        P: Into<Bytes>,
+    {
+        self.outstanding.push_back((payload.into(), Instant::now()));
+    }
+
+    /// Acknowledges a pong frame that has been received with `payload`, returning the time that
+    /// has elapsed since the matching ping was recorded. If no outstanding ping matches `payload`
+    /// then `None` is returned.
+    pub fn ack_pong(&mut self, payload: &[u8]) -> Option<Duration> {
+        let idx = self
+            .outstanding
+            .iter()
+            .position(|(ping, _)| ping.as_ref() == payload)?;
+        self.outstanding
+            .remove(idx)
+            .map(|(_, sent_at)| sent_at.elapsed())
+    }
+
+    /// Removes and returns the payloads of any pings that were recorded more than `timeout`
+    /// before `now`, in the order that they were recorded.
+    pub fn expire(&mut self, now: Instant, timeout: Duration) -> Vec<Bytes> {
+        let mut expired = Vec::new();
+        while let Some((_, sent_at)) = self.outstanding.front() {
+            if now.saturating_duration_since(*sent_at) <= timeout {
+                break;
+            }
+            if let Some((payload, _)) = self.outstanding.pop_front() {
+                expired.push(payload);
+            }
+        }
+        expired
+    }
+
+    /// Returns the number of pings that are awaiting a pong.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.len()
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::PingTracker;
+use bytes::Bytes;
+use std::time::{Duration, Instant};
+
+#[test]
+fn in_order() {
+    let mut tracker = PingTracker::new();
+    tracker.record_ping("1");
+    tracker.record_ping("2");
+    assert_eq!(tracker.outstanding(), 2);
+
+    assert!(tracker.ack_pong(b"1").is_some());
+    assert!(tracker.ack_pong(b"2").is_some());
+    assert_eq!(tracker.outstanding(), 0);
+}
+
+#[test]
+fn out_of_order() {
+    let mut tracker = PingTracker::new();
+    tracker.record_ping("1");
+    tracker.record_ping("2");
+    tracker.record_ping("3");
+
+    assert!(tracker.ack_pong(b"2").is_some());
+    assert!(tracker.ack_pong(b"3").is_some());
+    assert_eq!(tracker.outstanding(), 1);
+    assert!(tracker.ack_pong(b"1").is_some());
+    assert_eq!(tracker.outstanding(), 0);
+}
+
+#[test]
+fn duplicate_pong() {
+    let mut tracker = PingTracker::new();
+    tracker.record_ping("1");
+
+    assert!(tracker.ack_pong(b"1").is_some());
+    assert_eq!(tracker.ack_pong(b"1"), None);
+    assert_eq!(tracker.ack_pong(b"unsolicited"), None);
+}
+
+#[test]
+fn expire() {
+    let mut tracker = PingTracker::new();
+    tracker.record_ping("1");
+    tracker.record_ping("2");
+
+    let timeout = Duration::from_secs(30);
+    assert!(tracker.expire(Instant::now(), timeout).is_empty());
+
+    let expired = tracker.expire(Instant::now() + timeout * 2, timeout);
+    assert_eq!(expired, vec![Bytes::from("1"), Bytes::from("2")]);
+    assert_eq!(tracker.outstanding(), 0);
+    assert_eq!(tracker.ack_pong(b"1"), None);
+}
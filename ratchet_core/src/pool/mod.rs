@@ -0,0 +1,141 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[cfg(test)]
+mod tests;
+
+use bytes::BytesMut;
+use std::fmt::{Debug, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+/// A pool of buffers that are reused for encoding the frames that a WebSocket writes, instead of
+/// allocating a new buffer for each frame.
+///
+/// Only the write path uses a pool. Frames that are read are split from the WebSocket's read
+/// buffer, which is reused between frames, and their payloads are copied into the `read_buffer`
+/// that is provided by the caller; so reading a frame does not allocate a buffer for it.
+///
+/// A pool is cheap to clone and clones share the same buffers, so a single pool may be shared by
+/// many WebSockets and by both halves of a split WebSocket.
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    buffers: Mutex<Vec<BytesMut>>,
+    max_buffers: usize,
+    max_capacity: usize,
+}
+
+impl Debug for BufferPool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("available", &self.available())
+            .field("max_buffers", &self.inner.max_buffers)
+            .field("max_capacity", &self.inner.max_capacity)
+            .finish()
+    }
+}
+
+impl BufferPool {
+    /// Constructs a new, empty, pool that will retain at most `max_buffers` buffers. Buffers that
+    /// have grown beyond `max_capacity` bytes are freed instead of being returned to the pool so
+    /// that an occasional large frame does not pin its memory.
+    pub fn new(max_buffers: usize, max_capacity: usize) -> BufferPool {
+        BufferPool {
+            inner: Arc::new(Inner {
+                buffers: Mutex::new(Vec::with_capacity(max_buffers)),
+                max_buffers,
+                max_capacity,
+            }),
+        }
+    }
+
+    /// Returns the number of buffers that are available for reuse.
+    pub fn available(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Takes a buffer from the pool, or allocates a new one if the pool is empty, with room for
+    /// at least `capacity` bytes. The buffer is returned to the pool when it is dropped.
+    pub(crate) fn take(&self, capacity: usize) -> PooledBuffer {
+        let mut buffer = self.lock().pop().unwrap_or_default();
+        buffer.reserve(capacity);
+        PooledBuffer {
+            buffer,
+            pool: Some(self.clone()),
+        }
+    }
+
+    fn put(&self, mut buffer: BytesMut) {
+        if buffer.capacity() > self.inner.max_capacity {
+            return;
+        }
+
+        let mut buffers = self.lock();
+        if buffers.len() < self.inner.max_buffers {
+            buffer.clear();
+            buffers.push(buffer);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<BytesMut>> {
+        // A panic while the lock is held cannot leave the free list in an invalid state.
+        self.inner
+            .buffers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A buffer that is returned to the pool that it was taken from when it is dropped.
+#[derive(Debug)]
+pub(crate) struct PooledBuffer {
+    buffer: BytesMut,
+    pool: Option<BufferPool>,
+}
+
+impl PooledBuffer {
+    /// Allocates a buffer with room for `capacity` bytes that does not belong to a pool.
+    pub fn unpooled(capacity: usize) -> PooledBuffer {
+        PooledBuffer {
+            buffer: BytesMut::with_capacity(capacity),
+            pool: None,
+        }
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = BytesMut;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.put(std::mem::take(&mut self.buffer));
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{BufferPool, Message, NegotiatedExtension, NoExt, Role, WebSocket, WebSocketConfig};
+use bytes::{BufMut, BytesMut};
+use tokio::io::duplex;
+
+#[test]
+fn reuses_buffers() {
+    let pool = BufferPool::new(4, 1024);
+    assert_eq!(pool.available(), 0);
+
+    let mut buffer = pool.take(16);
+    buffer.put_slice(b"payload");
+    let ptr = buffer.as_ptr();
+    drop(buffer);
+    assert_eq!(pool.available(), 1);
+
+    let buffer = pool.take(16);
+    assert_eq!(pool.available(), 0);
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.as_ptr(), ptr);
+}
+
+#[test]
+fn bounded() {
+    let pool = BufferPool::new(2, 1024);
+    let buffers = (0..3).map(|_| pool.take(16)).collect::<Vec<_>>();
+    drop(buffers);
+    assert_eq!(pool.available(), 2);
+
+    let large = pool.take(4096);
+    let reused = pool.available();
+    drop(large);
+    assert_eq!(pool.available(), reused);
+}
+
+#[test]
+fn send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<BufferPool>();
+}
+
+#[tokio::test]
+async fn round_trip() {
+    let (client, server) = duplex(512);
+    let pool = BufferPool::new(1, 1024);
+
+    let mut client = WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        client,
+        NegotiatedExtension::from(NoExt),
+        BytesMut::new(),
+        Role::Client,
+    );
+    client.set_buffer_pool(Some(pool.clone()));
+
+    let mut server = WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        server,
+        NegotiatedExtension::from(NoExt),
+        BytesMut::new(),
+        Role::Server,
+    );
+
+    let mut buf = BytesMut::new();
+    for i in 0..64 {
+        let payload = format!("message {}", i).repeat(i % 4 + 1);
+        client.write_text(&payload).await.expect("Write failure");
+        assert_eq!(pool.available(), 1);
+
+        assert_eq!(server.read(&mut buf).await.unwrap(), Message::Text);
+        assert_eq!(buf.as_ref(), payload.as_bytes());
+        buf.clear();
+    }
+}
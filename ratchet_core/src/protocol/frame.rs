@@ -14,6 +14,7 @@
 
 use crate::errors::ProtocolError;
 use crate::protocol::{HeaderFlags, OpCode};
+use crate::ws::CONTROL_MAX_SIZE;
 use bytes::{BufMut, BytesMut};
 use either::Either;
 use std::convert::TryFrom;
@@ -22,34 +23,38 @@ use std::mem::size_of;
 
 const U16_MAX: usize = u16::MAX as usize;
 
-pub struct FramePrinter<'l>(pub &'l FrameHeader);
+/// Prints a frame header and the length of its payload.
+pub struct FramePrinter<'l>(pub &'l FrameHeader, pub usize);
 impl<'l> Display for FramePrinter<'l> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let FrameHeader {
-            opcode,
-            flags,
-            mask,
-        } = self.0;
-        write!(
-            f,
-            "opcode: {}, flags: {:?}, mask: {:?}",
-            opcode, flags, mask
-        )
+        let FramePrinter(
+            FrameHeader {
+                opcode,
+                flags,
+                mask,
+            },
+            len,
+        ) = self;
+        write_frame(f, opcode, flags, mask, *len)
     }
 }
 
-pub struct BorrowedFramePrinter<'l>(pub BorrowedFrameHeader<'l>);
+pub struct BorrowedFramePrinter<'l>(pub BorrowedFrameHeader<'l>, pub usize);
 impl<'l> BorrowedFramePrinter<'l> {
     pub fn new(
         opcode: &'l OpCode,
         flags: &'l HeaderFlags,
         mask: &'l Option<u32>,
+        len: usize,
     ) -> BorrowedFramePrinter<'l> {
-        BorrowedFramePrinter(BorrowedFrameHeader {
-            opcode,
-            flags,
-            mask,
-        })
+        BorrowedFramePrinter(
+            BorrowedFrameHeader {
+                opcode,
+                flags,
+                mask,
+            },
+            len,
+        )
     }
 }
 
@@ -60,14 +65,28 @@ impl<'l> Display for BorrowedFramePrinter<'l> {
             flags,
             mask,
         } = self.0;
-        write!(
-            f,
-            "opcode: {}, flags: {:?}, mask: {:?}",
-            opcode, flags, mask
-        )
+        write_frame(f, opcode, flags, mask, self.1)
     }
 }
 
+fn write_frame(
+    f: &mut Formatter<'_>,
+    opcode: &OpCode,
+    flags: &HeaderFlags,
+    mask: &Option<u32>,
+    len: usize,
+) -> std::fmt::Result {
+    write!(
+        f,
+        "opcode: {}, fin: {}, rsv: {:#05b}, masked: {}, len: {}",
+        opcode,
+        flags.is_fin(),
+        (flags.bits() >> 4) & 0b111,
+        mask.is_some(),
+        len
+    )
+}
+
 #[derive(Debug)]
 pub struct BorrowedFrameHeader<'l> {
     pub opcode: &'l OpCode,
@@ -140,6 +159,7 @@ impl FrameHeader {
     pub fn read_from(
         source: &[u8],
         is_server: bool,
+        accept_unmasked: bool,
         rsv_bits: u8,
         max_message_size: usize,
     ) -> Result<Either<(FrameHeader, usize, usize), usize>, ProtocolError> {
@@ -165,7 +185,7 @@ impl FrameHeader {
         let second = source[1];
         let masked = second & 0x80 != 0;
 
-        if !masked && is_server {
+        if !masked && is_server && !accept_unmasked {
             // rfc6455 § 6.1: Client must send masked data
             return Err(ProtocolError::UnmaskedFrame);
         } else if masked && !is_server {
@@ -184,6 +204,11 @@ impl FrameHeader {
             usize::from(payload_length)
         };
 
+        if opcode.is_control() && length > CONTROL_MAX_SIZE {
+            // rfc6455 § 5.5: All control frames MUST have a payload length of 125 bytes or less
+            return Err(ProtocolError::ControlFrameOverflow);
+        }
+
         if length > max_message_size {
             return Err(ProtocolError::FrameOverflow);
         }
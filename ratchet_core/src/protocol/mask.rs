@@ -12,8 +12,52 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
 const WORD_SIZE: usize = std::mem::size_of::<usize>() * 2;
 
+/// A generator of the 32-bit masking keys that are applied to frames sent by a client.
+///
+/// By default, a WebSocket uses a cryptographically secure random number generator for producing
+/// masking keys. Providing a different implementation is useful for producing deterministic output
+/// in tests, such as asserting the exact bytes of a masked frame.
+///
+/// This is implemented for any `FnMut() -> u32` closure.
+///
+/// # Note
+/// [RFC6455](https://datatracker.ietf.org/doc/html/rfc6455#section-5.3) requires that masking
+/// keys are unpredictable and so a fixed sequence of keys should **not** be used in production.
+pub trait MaskingKeyGen: Send + Sync + 'static {
+    /// Returns the masking key to use for the next frame.
+    fn next_key(&mut self) -> u32;
+}
+
+impl<F> MaskingKeyGen for F
+where
+    F: FnMut() -> u32 + Send + Sync + 'static,
+{
+    fn next_key(&mut self) -> u32 {
+        self()
+    }
+}
+
+/// The default masking key generator, backed by a cryptographically secure random number
+/// generator.
+pub struct EntropyKeyGen(StdRng);
+
+impl Default for EntropyKeyGen {
+    fn default() -> Self {
+        EntropyKeyGen(StdRng::from_entropy())
+    }
+}
+
+impl MaskingKeyGen for EntropyKeyGen {
+    fn next_key(&mut self) -> u32 {
+        self.0.gen()
+    }
+}
+
 #[inline]
 fn apply_mask_unoptimised(buf: &mut [u8], mask: [u8; 4]) {
     for (i, byte) in buf.iter_mut().enumerate() {
@@ -19,11 +19,12 @@ mod frame;
 mod mask;
 
 pub use frame::*;
-pub use mask::apply_mask;
+pub use mask::{apply_mask, EntropyKeyGen, MaskingKeyGen};
 
 use bytes::Bytes;
 use derive_more::Display;
 use std::convert::TryFrom;
+use std::time::Duration;
 use thiserror::Error;
 
 bitflags::bitflags! {
@@ -61,6 +62,28 @@ impl HeaderFlags {
 }
 
 /// A received WebSocket frame.
+///
+/// # Sending and receiving
+/// A `Message` describes a message that has been *received*. The payload of a text or binary
+/// message is written to the buffer that was provided to the read operation, rather than being
+/// contained in the `Message`, so that the buffer may be reused between reads. Messages are *sent*
+/// by providing the payload directly, using `write_text`, `write_binary` or `write` with a
+/// `PayloadType`:
+///
+/// ```no_run
+/// # use ratchet_core::{Error, Message, WebSocket, WebSocketStream};
+/// # use ratchet_ext::Extension;
+/// # use bytes::BytesMut;
+/// # async fn f<S: WebSocketStream, E: Extension>(mut websocket: WebSocket<S, E>) -> Result<(), Error> {
+/// websocket.write_text("hello").await?;
+///
+/// let mut buf = BytesMut::new();
+/// if let Message::Text = websocket.read(&mut buf).await? {
+///     assert_eq!(buf.as_ref(), b"hello");
+/// }
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Clone, Debug, PartialEq)]
 pub enum Message {
     /// A text message.
@@ -120,8 +143,30 @@ pub enum PayloadType {
     Pong,
 }
 
+impl PayloadType {
+    /// Returns the message type of this payload if it is a data payload.
+    pub(crate) fn data_type(self) -> Option<MessageType> {
+        match self {
+            PayloadType::Text => Some(MessageType::Text),
+            PayloadType::Binary => Some(MessageType::Binary),
+            PayloadType::Ping | PayloadType::Pong => None,
+        }
+    }
+}
+
+/// The kind of a data frame that has been read by `Receiver::read_fragment`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FragmentKind {
+    /// The first frame of a text message.
+    Text,
+    /// The first frame of a binary message.
+    Binary,
+    /// A continuation of the message that was started by a previous frame.
+    Continuation,
+}
+
 /// A message type to send.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum MessageType {
     /// A text message.
     Text,
@@ -132,18 +177,144 @@ pub enum MessageType {
 /// A configuration for building a WebSocket.
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub struct WebSocketConfig {
-    /// The maximum payload size that is permitted to be received.
+    /// The maximum payload size that is permitted to be received. A value of zero disables the
+    /// limit, which should only be used when the peer is trusted.
     pub max_message_size: usize,
+    /// If set, a server will fail the connection if the first data message that it receives is
+    /// not of this type. Control frames that are received before the first data message are
+    /// permitted.
+    ///
+    /// This has no effect on a client.
+    pub expected_first_opcode: Option<MessageType>,
+    /// Whether a server should close the connection with a policy violation (1008) close code if
+    /// the first data message that it receives does not match `expected_first_opcode`. If unset,
+    /// the connection is closed without sending a close frame. In both cases, `read` returns a
+    /// protocol error.
+    pub close_on_unexpected_opcode: bool,
+    /// Whether to automatically respond to any ping frames that are received with a pong frame.
+    /// This may be changed during a session using `set_auto_pong`.
+    pub auto_pong: bool,
+    /// If set, the minimum duration between pong frames that are automatically sent in response
+    /// to ping frames. Any ping frame that is received within this duration of the last automatic
+    /// pong is still returned by `read` but is not responded to. Defaults to `None`.
+    ///
+    /// This limits the amount of traffic that a peer may cause this endpoint to send by flooding
+    /// it with ping frames. However, [RFC6455](https://datatracker.ietf.org/doc/html/rfc6455#section-5.5.2)
+    /// requires that an endpoint responds to every ping frame that it receives, unless it has
+    /// not yet responded to a previous one, and so a peer that expects a response to each ping
+    /// may consider the connection unresponsive. Pings that are not responded to may still be
+    /// answered manually using `write_pong`.
+    pub min_auto_pong_interval: Option<Duration>,
+    /// Whether a server should accept unmasked frames from a client instead of failing the
+    /// connection. Defaults to `false`.
+    ///
+    /// # Note
+    /// This is **not** compliant with [RFC6455](https://datatracker.ietf.org/doc/html/rfc6455#section-5.1),
+    /// which requires that a server closes the connection upon receiving an unmasked frame, and
+    /// should only be enabled for interoperating with non-conformant clients. This has no effect
+    /// on a client.
+    pub accept_unmasked_frames: bool,
+    /// If set, the maximum duration that a server will wait for the peer's full upgrade request
+    /// to arrive and that a client will wait for the peer's upgrade response. The handshake fails
+    /// with a timeout error if it is exceeded.
+    pub handshake_timeout: Option<Duration>,
+    /// Whether to capture the raw bytes that are sent and received during the handshake. The
+    /// transcript is made available by `UpgradedClient` and `UpgradedServer`. Defaults to `false`.
+    pub capture_handshake: bool,
+    /// If set, text and binary messages whose payload is larger than this are automatically
+    /// split into a sequence of frames that are each no larger than this size. This is the
+    /// counterpart of `max_message_size` for peers which reject large frames. A value of zero is
+    /// treated as no limit. Defaults to `None`.
+    ///
+    /// This does not apply to control frames, which are always limited to 125 bytes, or to
+    /// `write_fragmented`, which uses the fragment size that it is provided with.
+    pub max_outgoing_frame_size: Option<usize>,
+    /// If set, the maximum duration that writing and flushing a single frame may take. If it is
+    /// exceeded, such as when the peer is not reading and the stream is backpressured, the write
+    /// fails with a timeout error. Defaults to `None`.
+    ///
+    /// A frame may have been partially written when the timeout elapses and so a timeout fails the
+    /// connection: it is moved to the closed state and any further writes, including those of
+    /// automatic pongs and close frames, fail with a `CloseCause::Error`. No close frame is sent
+    /// to the peer.
+    pub write_timeout: Option<Duration>,
+    /// Whether the payloads of text messages that are read are validated as UTF-8, failing the
+    /// read with an encoding error if they are not. Defaults to `false`, leaving validation to the
+    /// application.
+    ///
+    /// This applies to complete messages that are returned by `read` and not to the fragments
+    /// that are returned by `Receiver::read_fragment`.
+    pub validate_utf8: bool,
+    /// If set, the maximum number of frames that a text or binary message may be fragmented
+    /// into. If a message exceeds this, the read fails with a protocol error before the message
+    /// has been reassembled. This complements `max_message_size` by bounding the overhead of
+    /// peers which send many small fragments. A value of zero is treated as no limit. Defaults to
+    /// `None`.
+    ///
+    /// This does not apply to the fragments that are returned by `Receiver::read_fragment`.
+    pub max_fragments: Option<usize>,
 }
 
 impl Default for WebSocketConfig {
     fn default() -> Self {
         WebSocketConfig {
             max_message_size: 64 << 20,
+            expected_first_opcode: None,
+            close_on_unexpected_opcode: false,
+            auto_pong: true,
+            min_auto_pong_interval: None,
+            accept_unmasked_frames: false,
+            handshake_timeout: None,
+            capture_handshake: false,
+            max_outgoing_frame_size: None,
+            write_timeout: None,
+            validate_utf8: false,
+            max_fragments: None,
+        }
+    }
+}
+
+impl WebSocketConfig {
+    /// Returns a configuration for strict [RFC6455](https://datatracker.ietf.org/doc/html/rfc6455)
+    /// conformance, such as for running the Autobahn test suite.
+    ///
+    /// All leniency options are disabled: unmasked frames from a client are rejected, every ping
+    /// is responded to and the payloads of text messages are validated as UTF-8. Reserved
+    /// opcodes, unnegotiated RSV bits and invalid close codes are always rejected.
+    pub fn strict() -> WebSocketConfig {
+        WebSocketConfig {
+            validate_utf8: true,
+            ..Default::default()
         }
     }
 }
 
+/// The constraints that a WebSocket's role and negotiated extension impose on the frames that it
+/// sends.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrameConstraints {
+    /// Whether frames that are sent must be masked. This is only the case for clients.
+    pub must_mask: bool,
+    /// The maximum payload size of a control frame.
+    pub max_control_size: usize,
+    /// The reserved bits that may be set high in a frame's header, as a mask over the first byte
+    /// of the header.
+    pub rsv_bits: u8,
+}
+
+impl FrameConstraints {
+    /// Returns whether a frame with `opcode` may be sent.
+    pub fn is_opcode_allowed(&self, opcode: u8) -> bool {
+        OpCode::try_from(opcode).is_ok()
+    }
+
+    /// Returns whether a frame with the provided `rsv1`, `rsv2` and `rsv3` bits may be sent.
+    pub fn is_rsv_allowed(&self, rsv1: bool, rsv2: bool, rsv3: bool) -> bool {
+        let bits = (rsv1 as u8) << 6 | (rsv2 as u8) << 5 | (rsv3 as u8) << 4;
+        bits & !self.rsv_bits == 0
+    }
+}
+
 /// The role of a WebSocket.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Role {
@@ -0,0 +1,79 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// A future that resolves to the addresses of a host.
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'a>>;
+
+/// A trait for mapping a host and port to the socket addresses that a client should attempt to
+/// connect to, in order.
+pub trait Resolver: Send + Sync + 'static {
+    /// Resolve `host` and `port` to a list of socket addresses.
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> ResolveFuture<'a>;
+}
+
+impl<F> Resolver for F
+where
+    F: Fn(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + Sync + 'static,
+{
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> ResolveFuture<'a> {
+        let result = self(host, port);
+        Box::pin(async move { result })
+    }
+}
+
+/// The default resolver which uses the system's DNS via `tokio::net::lookup_host`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DnsResolver;
+
+impl Resolver for DnsResolver {
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> ResolveFuture<'a> {
+        Box::pin(async move { Ok(tokio::net::lookup_host((host, port)).await?.collect()) })
+    }
+}
+
+/// A shared, type-erased resolver.
+#[derive(Clone)]
+pub(crate) struct SharedResolver(Arc<dyn Resolver>);
+
+impl SharedResolver {
+    pub fn new<R>(resolver: R) -> SharedResolver
+    where
+        R: Resolver,
+    {
+        SharedResolver(Arc::new(resolver))
+    }
+
+    pub fn resolve<'a>(&'a self, host: &'a str, port: u16) -> ResolveFuture<'a> {
+        self.0.resolve(host, port)
+    }
+}
+
+impl Default for SharedResolver {
+    fn default() -> Self {
+        SharedResolver::new(DnsResolver)
+    }
+}
+
+impl Debug for SharedResolver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SharedResolver").finish()
+    }
+}
@@ -151,6 +151,7 @@ impl<T: Unpin> DerefMut for BiLockGuard<'_, T> {
     }
 }
 
+#[allow(dead_code)]
 pub struct ReuniteError<T>(pub BiLock<T>, pub BiLock<T>);
 
 impl<T> Debug for ReuniteError<T> {
@@ -0,0 +1,295 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use ratchet_ext::SplittableExtension;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{
+    CloseCause, CloseCode, CloseReason, Error, Message, PayloadType, Receiver, Sender, WebSocket,
+    WebSocketStream,
+};
+
+type SplitReceiver<S, E> = Receiver<S, <E as SplittableExtension>::SplitDecoder>;
+type SplitSender<S, E> = Sender<S, <E as SplittableExtension>::SplitEncoder>;
+
+type ReadFuture<S, E> = BoxFuture<'static, (SplitReceiver<S, E>, BytesMut, Result<Message, Error>)>;
+type WriteFuture<S, E> = BoxFuture<'static, (SplitSender<S, E>, Result<(), Error>)>;
+
+enum ReadState<S, E: SplittableExtension> {
+    // The receiver's read buffer, which must not be modified while a message is partially
+    // received.
+    Idle(SplitReceiver<S, E>, BytesMut),
+    Reading(ReadFuture<S, E>),
+    Terminated,
+}
+
+enum WriteState<S, E: SplittableExtension> {
+    Idle(SplitSender<S, E>),
+    Writing(WriteFuture<S, E>),
+    Closing(WriteFuture<S, E>),
+    Closed,
+}
+
+/// An adapter which tunnels a byte stream over a WebSocket's binary messages.
+///
+/// Each write is sent as a single binary message and reads yield the concatenated payloads of the
+/// binary messages that are received, so message boundaries are not preserved. Ping frames are
+/// answered in the same manner as `Receiver::read`. Shutting down the writer sends a close frame
+/// with a normal close code and reads yield end-of-file once a close frame has been received.
+///
+/// Receiving a text message fails the read with an `InvalidData` error.
+pub struct WebSocketByteStream<S, E: SplittableExtension> {
+    read: ReadState<S, E>,
+    // The unread remainder of the last binary message that was received.
+    ready: Bytes,
+    write: WriteState<S, E>,
+}
+
+// The states are never structurally pinned.
+impl<S, E: SplittableExtension> Unpin for WebSocketByteStream<S, E> {}
+
+impl<S, E: SplittableExtension> Debug for WebSocketByteStream<S, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let read = match &self.read {
+            ReadState::Idle(..) => "Idle",
+            ReadState::Reading(_) => "Reading",
+            ReadState::Terminated => "Terminated",
+        };
+        let write = match &self.write {
+            WriteState::Idle(_) => "Idle",
+            WriteState::Writing(_) => "Writing",
+            WriteState::Closing(_) => "Closing",
+            WriteState::Closed => "Closed",
+        };
+        f.debug_struct("WebSocketByteStream")
+            .field("read", &read)
+            .field("write", &write)
+            .finish()
+    }
+}
+
+impl<S, E> WebSocketByteStream<S, E>
+where
+    S: WebSocketStream + Send,
+    E: SplittableExtension + 'static,
+    E::SplitEncoder: Send,
+    E::SplitDecoder: Send,
+{
+    /// Constructs a new byte stream over the binary messages of `websocket`.
+    ///
+    /// # Errors
+    /// Errors if the WebSocket is already closed.
+    pub fn new(websocket: WebSocket<S, E>) -> Result<WebSocketByteStream<S, E>, Error> {
+        let (sender, receiver) = websocket.split()?;
+        Ok(WebSocketByteStream {
+            read: ReadState::Idle(receiver, BytesMut::new()),
+            ready: Bytes::new(),
+            write: WriteState::Idle(sender),
+        })
+    }
+
+    // Drives any pending write to completion.
+    fn poll_write_idle(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match std::mem::replace(&mut self.write, WriteState::Closed) {
+            WriteState::Writing(mut future) => match future.poll_unpin(cx) {
+                Poll::Ready((sender, result)) => {
+                    self.write = WriteState::Idle(sender);
+                    Poll::Ready(result.map_err(into_io_error))
+                }
+                Poll::Pending => {
+                    self.write = WriteState::Writing(future);
+                    Poll::Pending
+                }
+            },
+            state => {
+                self.write = state;
+                Poll::Ready(Ok(()))
+            }
+        }
+    }
+}
+
+async fn read<S, E>(
+    mut receiver: SplitReceiver<S, E>,
+    mut buf: BytesMut,
+) -> (SplitReceiver<S, E>, BytesMut, Result<Message, Error>)
+where
+    S: WebSocketStream + Send,
+    E: SplittableExtension,
+{
+    let result = receiver.read(&mut buf).await;
+    (receiver, buf, result)
+}
+
+async fn write<S, E>(
+    mut sender: SplitSender<S, E>,
+    payload: Bytes,
+) -> (SplitSender<S, E>, Result<(), Error>)
+where
+    S: WebSocketStream + Send,
+    E: SplittableExtension,
+{
+    let result = sender.write(payload, PayloadType::Binary).await;
+    (sender, result)
+}
+
+async fn close<S, E>(mut sender: SplitSender<S, E>) -> (SplitSender<S, E>, Result<(), Error>)
+where
+    S: WebSocketStream + Send,
+    E: SplittableExtension,
+{
+    let result = sender
+        .close(CloseReason::new(CloseCode::Normal, None))
+        .await;
+    (sender, result)
+}
+
+fn into_io_error(error: Error) -> io::Error {
+    io::Error::other(error)
+}
+
+impl<S, E> AsyncRead for WebSocketByteStream<S, E>
+where
+    S: WebSocketStream + Send,
+    E: SplittableExtension + 'static,
+    E::SplitEncoder: Send,
+    E::SplitDecoder: Send,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            match std::mem::replace(&mut this.read, ReadState::Terminated) {
+                ReadState::Idle(receiver, data) => {
+                    if this.ready.is_empty() {
+                        this.read = ReadState::Reading(read::<S, E>(receiver, data).boxed());
+                    } else {
+                        let len = this.ready.len().min(buf.remaining());
+                        buf.put_slice(&this.ready[..len]);
+                        this.ready.advance(len);
+                        this.read = ReadState::Idle(receiver, data);
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                ReadState::Reading(mut future) => {
+                    let (receiver, mut data, result) = match future.poll_unpin(cx) {
+                        Poll::Ready(output) => output,
+                        Poll::Pending => {
+                            this.read = ReadState::Reading(future);
+                            return Poll::Pending;
+                        }
+                    };
+
+                    match result {
+                        Ok(Message::Binary) => {
+                            this.ready = data.split().freeze();
+                            this.read = ReadState::Idle(receiver, data);
+                        }
+                        // Control frames are handled by the receiver and may be received between
+                        // the frames of a message, which remain in the read buffer.
+                        Ok(Message::Ping(_) | Message::Pong(_)) => {
+                            this.read = ReadState::Idle(receiver, data);
+                        }
+                        Ok(Message::Text) => {
+                            return Poll::Ready(Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "Received a text message",
+                            )));
+                        }
+                        Ok(Message::Close(_)) => return Poll::Ready(Ok(())),
+                        // The close frame that was sent by the writer has been echoed by the peer.
+                        Err(e) if e.downcast_ref() == Some(&CloseCause::Stopped) => {
+                            return Poll::Ready(Ok(()))
+                        }
+                        Err(e) => return Poll::Ready(Err(into_io_error(e))),
+                    }
+                }
+                ReadState::Terminated => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+impl<S, E> AsyncWrite for WebSocketByteStream<S, E>
+where
+    S: WebSocketStream + Send,
+    E: SplittableExtension + 'static,
+    E::SplitEncoder: Send,
+    E::SplitDecoder: Send,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if let Err(e) = futures::ready!(this.poll_write_idle(cx)) {
+            return Poll::Ready(Err(e));
+        }
+
+        match std::mem::replace(&mut this.write, WriteState::Closed) {
+            WriteState::Idle(sender) => {
+                let payload = Bytes::copy_from_slice(buf);
+                this.write = WriteState::Writing(write::<S, E>(sender, payload).boxed());
+                Poll::Ready(Ok(buf.len()))
+            }
+            state => {
+                this.write = state;
+                Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_write_idle(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Err(e) = futures::ready!(this.poll_write_idle(cx)) {
+            return Poll::Ready(Err(e));
+        }
+
+        loop {
+            match std::mem::replace(&mut this.write, WriteState::Closed) {
+                WriteState::Idle(sender) => {
+                    this.write = WriteState::Closing(close::<S, E>(sender).boxed());
+                }
+                WriteState::Closing(mut future) => {
+                    return match future.poll_unpin(cx) {
+                        Poll::Ready((_, result)) => Poll::Ready(result.map_err(into_io_error)),
+                        Poll::Pending => {
+                            this.write = WriteState::Closing(future);
+                            Poll::Pending
+                        }
+                    };
+                }
+                WriteState::Writing(_) => unreachable!("Pending writes have completed"),
+                WriteState::Closed => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::{Bytes, BytesMut};
+use futures::future::{select, Either};
+use ratchet_ext::SplittableExtension;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::{
+    CloseCause, CloseCode, CloseReason, Error, Message, PayloadType, WebSocket, WebSocketStream,
+};
+
+/// A message sent to a WebSocket that has been bridged using `WebSocket::into_channels`.
+pub type OutboundMessage = (PayloadType, Bytes);
+
+/// A message received from a WebSocket that has been bridged using `WebSocket::into_channels`.
+///
+/// The payload of any text or binary messages is yielded alongside the message.
+pub type InboundMessage = (Message, Bytes);
+
+/// The channels and background task produced by `WebSocket::into_channels`.
+pub type ChannelBridge = (
+    mpsc::Sender<OutboundMessage>,
+    mpsc::Receiver<InboundMessage>,
+    JoinHandle<Result<(), Error>>,
+);
+
+pub fn bridge<S, E>(
+    websocket: WebSocket<S, E>,
+    inbound_capacity: usize,
+    outbound_capacity: usize,
+) -> ChannelBridge
+where
+    S: WebSocketStream + Send,
+    E: SplittableExtension + Send + 'static,
+{
+    let (outbound_tx, outbound_rx) = mpsc::channel(outbound_capacity);
+    let (inbound_tx, inbound_rx) = mpsc::channel(inbound_capacity);
+    let handle = tokio::spawn(run(websocket, inbound_tx, outbound_rx));
+
+    (outbound_tx, inbound_rx, handle)
+}
+
+async fn run<S, E>(
+    websocket: WebSocket<S, E>,
+    inbound_tx: mpsc::Sender<InboundMessage>,
+    mut outbound_rx: mpsc::Receiver<OutboundMessage>,
+) -> Result<(), Error>
+where
+    S: WebSocketStream + Send,
+    E: SplittableExtension + Send + 'static,
+{
+    let (mut sender, mut receiver) = websocket.split()?;
+
+    let read = Box::pin(async move {
+        let mut buf = BytesMut::new();
+        loop {
+            let message = match receiver.read(&mut buf).await {
+                Ok(message) => message,
+                // The close frame that was sent by the write half has been echoed by the peer.
+                Err(e) if e.downcast_ref() == Some(&CloseCause::Stopped) => break Ok(()),
+                Err(e) => break Err(e),
+            };
+            let is_close = message.is_close();
+
+            match message {
+                Message::Text | Message::Binary | Message::Close(_) => {
+                    // A full inbound channel will stop the socket from being read from until the
+                    // application has caught up.
+                    let payload = buf.split().freeze();
+                    if inbound_tx.send((message, payload)).await.is_err() || is_close {
+                        break Ok(());
+                    }
+                }
+                // Control frames are handled by the receiver.
+                Message::Ping(_) | Message::Pong(_) => {}
+            }
+        }
+    });
+
+    let write = Box::pin(async move {
+        while let Some((payload_type, payload)) = outbound_rx.recv().await {
+            sender.write(payload, payload_type).await?;
+        }
+        // The application has dropped its sender so the session is closed. The read half will
+        // complete once the peer has echoed the close frame.
+        sender
+            .close(CloseReason::new(CloseCode::Normal, None))
+            .await
+    });
+
+    match select(read, write).await {
+        Either::Left((result, _)) => result,
+        Either::Right((Ok(()), read)) => read.await,
+        Either::Right((Err(e), _)) => Err(e),
+    }
+}
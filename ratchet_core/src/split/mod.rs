@@ -13,23 +13,34 @@
 // limitations under the License.
 
 use std::fmt::Debug;
+use std::future::Future;
+use std::pin::pin;
 use std::sync::atomic::AtomicU8;
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
 
 use bitflags::_core::sync::atomic::Ordering;
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use log::{error, trace};
 use tokio::io::AsyncWriteExt;
 
 use bilock::{bilock, BiLock};
 use ratchet_ext::{ExtensionDecoder, ExtensionEncoder, ReunitableExtension, SplittableExtension};
 
+pub use byte_stream::WebSocketByteStream;
+pub use channels::{bridge, ChannelBridge, InboundMessage, OutboundMessage};
+pub use stream::ReceiverStream;
+
 use crate::ext::NegotiatedExtension;
 use crate::framed::{
-    read_next, write_close, write_fragmented, CodecFlags, FramedIoParts, FramedRead, FramedWrite,
-    Item,
+    read_next, read_next_fragment, write_close, write_fragmented, ByteCounts, CodecFlags,
+    FirstMessage, FragmentItem, FramedIoParts, FramedRead, FramedWrite, Item, PongLimit, Slices,
+};
+use crate::handshake::with_timeout;
+use crate::protocol::{
+    CloseReason, ControlCode, DataCode, FragmentKind, HeaderFlags, MessageType, OpCode,
 };
-use crate::protocol::{CloseReason, ControlCode, DataCode, HeaderFlags, MessageType, OpCode};
 use crate::ws::{extension_encode, CloseState, CONTROL_MAX_SIZE};
 use crate::{
     framed, CloseCause, Error, ErrorKind, Message, PayloadType, ProtocolError, Role, WebSocket,
@@ -37,6 +48,9 @@ use crate::{
 };
 
 mod bilock;
+mod byte_stream;
+mod channels;
+mod stream;
 #[cfg(test)]
 mod tests;
 
@@ -61,6 +75,7 @@ pub fn split<S, E>(
     framed: framed::FramedIo<S>,
     control_buffer: BytesMut,
     extension: NegotiatedExtension<E>,
+    subprotocol: Option<String>,
 ) -> (Sender<S, E::SplitEncoder>, Receiver<S, E::SplitDecoder>)
 where
     S: WebSocketStream,
@@ -72,9 +87,13 @@ where
         writer,
         flags,
         max_message_size,
+        first_message,
+        auto_pong,
+        pong_limit,
     } = framed.into_parts();
 
     let close_state = Arc::new(AtomicU8::new(STATE_OPEN));
+    let byte_counts = writer.counts().clone();
     let (read_half, write_half) = bilock(io);
     let (sender_writer, reader_writer) = bilock(WriteHalf {
         control_buffer,
@@ -93,8 +112,10 @@ where
     let sender = Sender {
         role,
         close_state: close_state.clone(),
+        byte_counts,
         split_writer: sender_writer,
         ext_encoder,
+        subprotocol,
     };
     let receiver = Receiver {
         role,
@@ -102,6 +123,9 @@ where
         framed: FramedIo {
             flags,
             max_message_size,
+            first_message,
+            auto_pong,
+            pong_limit,
             read_half,
             reader,
             split_writer: reader_writer,
@@ -136,33 +160,35 @@ where
         let buf = buf_ref.as_ref();
 
         match message_type {
-            PayloadType::Text => writer
-                .write(
-                    split_writer,
-                    is_server,
-                    OpCode::DataCode(DataCode::Text),
-                    header_flags,
-                    buf,
-                    |payload, header| extension_encode(extension, payload, header),
-                )
-                .await
-                .map_err(Into::into),
-            PayloadType::Binary => writer
-                .write(
-                    split_writer,
-                    is_server,
-                    OpCode::DataCode(DataCode::Binary),
-                    header_flags,
-                    buf,
-                    |payload, header| extension_encode(extension, payload, header),
-                )
-                .await
-                .map_err(Into::into),
+            PayloadType::Text => {
+                writer
+                    .write(
+                        split_writer,
+                        is_server,
+                        OpCode::DataCode(DataCode::Text),
+                        header_flags,
+                        buf,
+                        |payload, header| extension_encode(extension, payload, header),
+                    )
+                    .await
+            }
+            PayloadType::Binary => {
+                writer
+                    .write(
+                        split_writer,
+                        is_server,
+                        OpCode::DataCode(DataCode::Binary),
+                        header_flags,
+                        buf,
+                        |payload, header| extension_encode(extension, payload, header),
+                    )
+                    .await
+            }
             PayloadType::Ping => {
                 if buf.len() > CONTROL_MAX_SIZE {
                     Err(Error::with_cause(
                         ErrorKind::Protocol,
-                        ProtocolError::FrameOverflow,
+                        ProtocolError::ControlFrameOverflow,
                     ))
                 } else {
                     control_buffer.clear();
@@ -178,14 +204,13 @@ where
                             |payload, header| extension_encode(extension, payload, header),
                         )
                         .await
-                        .map_err(Into::into)
                 }
             }
             PayloadType::Pong => {
                 if buf.len() > CONTROL_MAX_SIZE {
                     Err(Error::with_cause(
                         ErrorKind::Protocol,
-                        ProtocolError::FrameOverflow,
+                        ProtocolError::ControlFrameOverflow,
                     ))
                 } else {
                     writer
@@ -198,7 +223,6 @@ where
                             |payload, header| extension_encode(extension, payload, header),
                         )
                         .await
-                        .map_err(Into::into)
                 }
             }
         }
@@ -220,6 +244,9 @@ struct WriteHalf<S> {
 struct FramedIo<S, E> {
     flags: CodecFlags,
     max_message_size: usize,
+    first_message: FirstMessage,
+    auto_pong: bool,
+    pong_limit: PongLimit,
     read_half: BiLock<S>,
     reader: FramedRead,
     split_writer: BiLock<WriteHalf<S>>,
@@ -227,12 +254,21 @@ struct FramedIo<S, E> {
 }
 
 /// An owned write half of a WebSocket connection.
+///
+/// # Ordering
+/// Data frames are not queued by a `Sender`: every frame is written to and flushed on the
+/// underlying stream before the call that sent it returns. A control frame, including a pong
+/// that the `Receiver` sends in response to a ping, is therefore never held behind a backlog of
+/// data frames and waits only for a write that is already in progress to complete. A message
+/// that is split into fragments is written in full before any other frame is written.
 #[derive(Debug)]
 pub struct Sender<S, E> {
     role: Role,
     close_state: Arc<AtomicU8>,
+    byte_counts: Arc<ByteCounts>,
     split_writer: BiLock<WriteHalf<S>>,
     ext_encoder: NegotiatedExtension<E>,
+    subprotocol: Option<String>,
 }
 
 impl<S, E> Sender<S, E>
@@ -244,6 +280,8 @@ where
     ///
     /// # Errors
     /// Errors if `receiver` is not paired with this sender.
+    // The halves are returned by value on failure so that they may be reused.
+    #[allow(clippy::result_large_err)]
     pub fn reunite<Ext>(
         self,
         receiver: Receiver<S, Ext::SplitDecoder>,
@@ -260,6 +298,11 @@ where
         self.role
     }
 
+    /// Returns the subprotocol that was negotiated for this session, if any.
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.subprotocol.as_deref()
+    }
+
     /// Returns whether this WebSocket is closed.
     pub fn is_closed(&self) -> bool {
         self.close_state.load(Ordering::SeqCst) == STATE_CLOSED
@@ -270,6 +313,18 @@ where
         matches!(self.close_state.load(Ordering::SeqCst), STATE_OPEN)
     }
 
+    /// Returns whether a close frame has been sent or received but the closing handshake has not
+    /// yet completed.
+    pub fn is_closing(&self) -> bool {
+        self.close_state.load(Ordering::SeqCst) == STATE_CLOSING
+    }
+
+    /// Returns the number of raw bytes that have been `(read, written)` by both halves of this
+    /// WebSocket, including frame headers.
+    pub fn byte_counts(&self) -> (u64, u64) {
+        self.byte_counts.get()
+    }
+
     /// Constructs a new text WebSocket message with a payload of `data`.
     pub async fn write_text<I>(&mut self, data: I) -> Result<(), Error>
     where
@@ -312,7 +367,32 @@ where
         }
 
         let writer = &mut *self.split_writer.lock().await;
-        writer
+        let buf = buf.as_ref();
+
+        if let (Some(data_type), Some(fragment_size)) = (
+            message_type.data_type(),
+            writer.writer.fragment_size(buf.len()),
+        ) {
+            let WriteHalf {
+                split_writer,
+                writer,
+                ..
+            } = writer;
+            let ext_encoder = &mut self.ext_encoder;
+            let result = write_fragmented(
+                split_writer,
+                writer,
+                buf,
+                data_type,
+                fragment_size,
+                self.role.is_server(),
+                |payload, header| extension_encode(ext_encoder, payload, header),
+            )
+            .await;
+            return fail_on_timeout(&self.close_state, result);
+        }
+
+        let result = writer
             .write(
                 buf,
                 message_type,
@@ -320,7 +400,8 @@ where
                 self.role.is_server(),
                 &mut self.ext_encoder,
             )
-            .await
+            .await;
+        fail_on_timeout(&self.close_state, result)
     }
 
     /// Sends a new WebSocket message of `message_type` and with a payload of `buf_ref` and chunked
@@ -344,7 +425,7 @@ where
             ..
         } = &mut *self.split_writer.lock().await;
         let ext_encoder = &mut self.ext_encoder;
-        write_fragmented(
+        let result = write_fragmented(
             split_writer,
             writer,
             buf,
@@ -353,11 +434,63 @@ where
             self.role.is_server(),
             |payload, header| extension_encode(ext_encoder, payload, header),
         )
-        .await
+        .await;
+        fail_on_timeout(&self.close_state, result)
+    }
+
+    /// Sends a single WebSocket message of `message_type` whose payload is the concatenation of
+    /// `slices`. Masking is applied across the whole concatenation, so the peer receives the same
+    /// message as if the slices had been joined before calling [`Sender::write`].
+    ///
+    /// If this is a server and no extension has been negotiated then the slices are written
+    /// directly to the stream without being copied into an intermediate buffer.
+    pub async fn write_vectored(
+        &mut self,
+        slices: &[&[u8]],
+        message_type: MessageType,
+    ) -> Result<(), Error> {
+        if !self.is_active() {
+            return Err(Error::with_cause(ErrorKind::Close, CloseCause::Error));
+        }
+
+        let opcode = OpCode::DataCode(match message_type {
+            MessageType::Text => DataCode::Text,
+            MessageType::Binary => DataCode::Binary,
+        });
+        let WriteHalf {
+            split_writer,
+            writer,
+            ..
+        } = &mut *self.split_writer.lock().await;
+        let ext_encoder = &mut self.ext_encoder;
+
+        let result = if self.role.is_server() && !ext_encoder.is_negotiated() {
+            writer
+                .write_unmasked(split_writer, opcode, HeaderFlags::FIN, Slices::new(slices))
+                .await
+        } else {
+            writer
+                .write_slices(
+                    split_writer,
+                    self.role.is_server(),
+                    opcode,
+                    HeaderFlags::FIN,
+                    slices,
+                    |payload, header| extension_encode(ext_encoder, payload, header),
+                )
+                .await
+        };
+        fail_on_timeout(&self.close_state, result)
     }
 
     /// Close this WebSocket with the reason provided.
     ///
+    /// The close frame is written and flushed but this does not wait for the peer to echo it, nor
+    /// is the stream shut down. To complete the closing handshake the `Receiver` should continue to
+    /// be read from until the peer's close frame is received, such as by using
+    /// `Receiver::drain_until_close`. If a graceful shutdown is not required, such as during
+    /// process termination, then both halves may be dropped once this returns.
+    ///
     /// If the WebSocket is already closed then `Ok(())` is returned.
     pub async fn close(&mut self, reason: CloseReason) -> Result<(), Error> {
         if !self.is_active() {
@@ -371,7 +504,8 @@ where
             writer,
             ..
         } = &mut *self.split_writer.lock().await;
-        write_close(split_writer, writer, reason, self.role.is_server()).await
+        let result = write_close(split_writer, writer, reason, self.role.is_server()).await;
+        fail_on_timeout(&self.close_state, result)
     }
 }
 
@@ -393,6 +527,39 @@ where
         self.role
     }
 
+    /// Sets whether to automatically respond to any ping frames that are received with a pong
+    /// frame. If disabled, received ping frames are still returned by `read` as `Message::Ping`.
+    pub fn set_auto_pong(&mut self, auto_pong: bool) {
+        self.framed.auto_pong = auto_pong;
+    }
+
+    /// Sets the maximum size of messages that may be received. The new limit applies from the
+    /// next message that is read; a fragmented message that is partially read is still bounded
+    /// by the limit that was in effect when it started. A size of zero disables the limit.
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.framed.max_message_size = max_message_size;
+    }
+
+    /// Returns whether the RSV1 bit was set on the most recently read data message. When the
+    /// permessage-deflate extension has been negotiated, this indicates that the message was
+    /// compressed by the peer.
+    pub fn last_message_compressed(&self) -> bool {
+        self.framed.reader.last_message_compressed()
+    }
+
+    /// Converts this receiver into a `Stream` of the messages that it receives.
+    ///
+    /// `Stream::poll_next` returns `Poll::Pending` until a complete message has been received and
+    /// retains any partially read message between polls, which allows messages to be polled for
+    /// from a custom event loop without managing a read buffer.
+    pub fn into_stream(self) -> ReceiverStream<S, E>
+    where
+        S: Send,
+        E: Send + 'static,
+    {
+        ReceiverStream::new(self)
+    }
+
     /// Attempt to read some data from the WebSocket. Returning either the type of the message
     /// received or the error that was produced.
     ///
@@ -406,8 +573,79 @@ where
     /// these may be interleaved between data frames. In the event of one being received while
     /// reading a continuation, this function will then yield `Message::Ping` and the `read_buffer`
     /// will contain the data received up to that point. The callee must ensure that the contents
-    /// of `read_buffer` are **not** then modified before calling `read` again.
+    /// of `read_buffer` are **not** then modified before calling `read` again. If a close frame
+    /// is received while reading a continuation, the data received up to that point is removed
+    /// from `read_buffer`.
+    ///
+    /// If a close frame has been sent then any data frames that the peer sent before receiving it
+    /// continue to be returned. Once the peer's close frame is received, the closing handshake is
+    /// complete and a close error with a cause of `CloseCause::Stopped` is returned.
+    ///
+    /// # Cancel safety
+    /// This method is cancellation safe while it is waiting for data in the same manner as
+    /// `WebSocket::read`.
     pub async fn read(&mut self, read_buffer: &mut BytesMut) -> Result<Message, Error> {
+        self.read_message(read_buffer, &mut None).await
+    }
+
+    /// Attempt to read a message from the WebSocket without awaiting, for use when implementing
+    /// a custom future or event loop. Returns `Poll::Pending`, and schedules the current task to
+    /// be woken, if a complete message has not yet been received. Otherwise, the message is
+    /// returned and `read_buffer` is populated in the same manner as `read`.
+    ///
+    /// Any frames of a message that have been received are retained between calls provided that
+    /// the same `read_buffer` is provided to each call and that it is not modified. If
+    /// `Poll::Pending` is returned while a pong or close frame is being written in response to a
+    /// frame received from the peer then the response may be partially written, in the same
+    /// manner as when a `read` future is dropped.
+    pub fn poll_read(
+        &mut self,
+        cx: &mut Context<'_>,
+        read_buffer: &mut BytesMut,
+    ) -> Poll<Result<Message, Error>> {
+        let read = pin!(self.read(read_buffer));
+        read.poll(cx)
+    }
+
+    /// Read and discard any messages that are received until the peer's close frame arrives,
+    /// returning the reason that the peer closed the connection with. Ping frames are responded
+    /// to as they are by `read`. This is intended for use after a close frame has been sent, to
+    /// wait for the peer to complete the closing handshake without surfacing any remaining data.
+    ///
+    /// If the peer initiated the close then the close frame is echoed in the same manner as
+    /// `read`.
+    ///
+    /// # Errors
+    /// Errors with a timeout error if the peer's close frame has not been received within
+    /// `timeout`, or with any error that is produced by `read`.
+    pub async fn drain_until_close(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<CloseReason>, Error> {
+        let mut read_buffer = BytesMut::new();
+        let mut close_reason = None;
+
+        let drain = async {
+            loop {
+                match self.read_message(&mut read_buffer, &mut close_reason).await {
+                    Ok(Message::Text | Message::Binary) => read_buffer.clear(),
+                    Ok(Message::Ping(_) | Message::Pong(_)) => {}
+                    Ok(Message::Close(reason)) => break Ok(reason),
+                    Err(e) if matches!(e.downcast_ref(), Some(CloseCause::Stopped)) => {
+                        break Ok(close_reason.take())
+                    }
+                    Err(e) => break Err(e),
+                }
+            }
+        };
+        with_timeout(Some(timeout), drain).await
+    }
+
+    async fn read_message(
+        &mut self,
+        read_buffer: &mut BytesMut,
+        close_reason: &mut Option<CloseReason>,
+    ) -> Result<Message, Error> {
         if self.is_closed() {
             return Err(Error::with_cause(ErrorKind::Close, CloseCause::Error));
         }
@@ -421,6 +659,9 @@ where
         let FramedIo {
             flags,
             max_message_size,
+            first_message,
+            auto_pong,
+            pong_limit,
             read_half,
             reader,
             split_writer,
@@ -439,9 +680,39 @@ where
         .await
         {
             Ok(item) => match item {
-                Item::Binary => Ok(Message::Binary),
-                Item::Text => Ok(Message::Text),
+                Item::Binary => match first_message.check(MessageType::Binary) {
+                    Ok(()) => Ok(Message::Binary),
+                    Err(e) => {
+                        fail_first_message(
+                            close_state,
+                            &mut *split_writer.lock().await,
+                            is_server,
+                            first_message,
+                            e,
+                        )
+                        .await
+                    }
+                },
+                Item::Text => match first_message.check(MessageType::Text) {
+                    Ok(()) => Ok(Message::Text),
+                    Err(e) => {
+                        fail_first_message(
+                            close_state,
+                            &mut *split_writer.lock().await,
+                            is_server,
+                            first_message,
+                            e,
+                        )
+                        .await
+                    }
+                },
                 Item::Ping(payload) => {
+                    let ret = payload.clone().freeze();
+                    if !(*auto_pong && pong_limit.permit()) {
+                        trace!("Received a ping frame");
+                        return Ok(Message::Ping(ret));
+                    }
+
                     trace!("Received a ping frame. Responding with pong");
 
                     let WriteHalf {
@@ -450,8 +721,7 @@ where
                         ..
                     } = &mut *split_writer.lock().await;
 
-                    let ret = payload.clone().freeze();
-                    writer
+                    let result = writer
                         .write(
                             split_writer,
                             is_server,
@@ -460,7 +730,8 @@ where
                             payload,
                             |_, _| Ok(()),
                         )
-                        .await?;
+                        .await;
+                    fail_on_timeout(close_state, result)?;
                     Ok(Message::Ping(ret))
                 }
                 Item::Pong(payload) => {
@@ -475,6 +746,7 @@ where
                     Ok(Message::Pong(payload.freeze()))
                 }
                 Item::Close(reason) => {
+                    close_reason.clone_from(&reason);
                     close(
                         close_state,
                         &mut *split_writer.lock().await,
@@ -499,6 +771,128 @@ where
         }
     }
 
+    /// Attempt to read the next data frame from the WebSocket without waiting for the rest of its
+    /// message. Returning the kind of the frame, its payload and whether it is the final frame of
+    /// its message.
+    ///
+    /// This allows very large messages to be processed as they arrive instead of being coalesced
+    /// into a single buffer as `read` does. The maximum message size is only applied to each
+    /// frame. Any control frames that are received are handled in the same manner as `read` but
+    /// are not returned. If a close frame is received then the closing handshake is completed and
+    /// a close error with a cause of `CloseCause::Stopped` is returned.
+    ///
+    /// # Errors
+    /// In addition to the errors produced by `read`, an extension error is returned if a frame
+    /// has been encoded by the negotiated extension, as extensions may only decode complete
+    /// messages.
+    pub async fn read_fragment(&mut self) -> Result<(FragmentKind, Bytes, bool), Error> {
+        if self.is_closed() {
+            return Err(Error::with_cause(ErrorKind::Close, CloseCause::Error));
+        }
+
+        let Receiver {
+            role,
+            close_state,
+            framed,
+        } = self;
+        let FramedIo {
+            flags,
+            max_message_size,
+            first_message,
+            auto_pong,
+            pong_limit,
+            read_half,
+            reader,
+            split_writer,
+            ext_decoder,
+        } = framed;
+        let is_server = role.is_server();
+
+        loop {
+            let item =
+                read_next_fragment(read_half, reader, flags, *max_message_size, ext_decoder).await;
+            let result = match item {
+                Ok(FragmentItem::Data(kind, payload, fin)) => {
+                    let message_type = match kind {
+                        FragmentKind::Text => Some(MessageType::Text),
+                        FragmentKind::Binary => Some(MessageType::Binary),
+                        FragmentKind::Continuation => None,
+                    };
+                    match message_type.map(|ty| first_message.check(ty)) {
+                        Some(Err(e)) => {
+                            fail_first_message(
+                                close_state,
+                                &mut *split_writer.lock().await,
+                                is_server,
+                                first_message,
+                                e,
+                            )
+                            .await
+                        }
+                        _ => return Ok((kind, payload.freeze(), fin)),
+                    }
+                }
+                Ok(FragmentItem::Control(Item::Ping(payload))) => {
+                    if *auto_pong && pong_limit.permit() {
+                        trace!("Received a ping frame. Responding with pong");
+
+                        let WriteHalf {
+                            split_writer,
+                            writer,
+                            ..
+                        } = &mut *split_writer.lock().await;
+
+                        let result = writer
+                            .write(
+                                split_writer,
+                                is_server,
+                                OpCode::ControlCode(ControlCode::Pong),
+                                HeaderFlags::FIN,
+                                payload,
+                                |_, _| Ok(()),
+                            )
+                            .await;
+                        fail_on_timeout(close_state, result)?;
+                    }
+                    continue;
+                }
+                Ok(FragmentItem::Control(Item::Pong(_))) => {
+                    split_writer.lock().await.control_buffer.clear();
+                    continue;
+                }
+                Ok(FragmentItem::Control(Item::Close(reason))) => {
+                    close(
+                        close_state,
+                        &mut *split_writer.lock().await,
+                        is_server,
+                        reason,
+                        None,
+                    )
+                    .await
+                }
+                Ok(FragmentItem::Control(_)) => {
+                    unreachable!("Data frames are not yielded as control items")
+                }
+                Err(e) => {
+                    error!("WebSocket read failure: {:?}", e);
+                    close(
+                        close_state,
+                        &mut *split_writer.lock().await,
+                        is_server,
+                        None,
+                        Some(e),
+                    )
+                    .await
+                }
+            };
+
+            return match result {
+                Ok(_) => Err(Error::with_cause(ErrorKind::Close, CloseCause::Stopped)),
+                Err(e) => Err(e),
+            };
+        }
+    }
+
     /// Close this WebSocket with the reason provided.
     ///
     /// If the WebSocket is already closed then `Ok(())` is returned.
@@ -514,7 +908,8 @@ where
             writer,
             ..
         } = &mut *self.framed.split_writer.lock().await;
-        write_close(split_writer, writer, reason, self.role.is_server()).await
+        let result = write_close(split_writer, writer, reason, self.role.is_server()).await;
+        fail_on_timeout(&self.close_state, result)
     }
 
     /// Returns whether this WebSocket is closed.
@@ -526,6 +921,53 @@ where
     pub fn is_active(&self) -> bool {
         matches!(self.close_state.load(Ordering::SeqCst), STATE_OPEN)
     }
+
+    /// Returns whether a close frame has been sent or received but the closing handshake has not
+    /// yet completed.
+    pub fn is_closing(&self) -> bool {
+        self.close_state.load(Ordering::SeqCst) == STATE_CLOSING
+    }
+
+    /// Returns the number of raw bytes that have been `(read, written)` by both halves of this
+    /// WebSocket, including frame headers.
+    pub fn byte_counts(&self) -> (u64, u64) {
+        self.framed.reader.counts().get()
+    }
+}
+
+/// Moves `close_state` to closed if `result` is a write timeout. The frame that was being written
+/// may have been partially written and so the connection may not be written to again.
+fn fail_on_timeout<T>(close_state: &AtomicU8, result: Result<T, Error>) -> Result<T, Error> {
+    if matches!(&result, Err(e) if e.is_timeout()) {
+        close_state.store(STATE_CLOSED, Ordering::SeqCst);
+    }
+    result
+}
+
+/// Fails the connection after the first data message that was received did not match the
+/// expected type. A policy violation close frame is sent if `close_on_unexpected_opcode` is set,
+/// otherwise the connection is closed without sending a close frame.
+async fn fail_first_message<S>(
+    close_state: &AtomicU8,
+    framed: &mut WriteHalf<S>,
+    is_server: bool,
+    first_message: &FirstMessage,
+    error: Error,
+) -> Result<Message, Error>
+where
+    S: WebSocketStream,
+{
+    match first_message.close_reason() {
+        Some(reason) => close(close_state, framed, is_server, Some(reason), Some(error)).await,
+        None => {
+            if is_server {
+                // 7.1.1: the TCP stream should be closed first by the server
+                framed.close().await;
+            }
+            close_state.store(STATE_CLOSED, Ordering::SeqCst);
+            Err(error)
+        }
+    }
 }
 
 async fn close<S>(
@@ -609,6 +1051,7 @@ pub struct ReuniteError<S, E, D> {
 
 /// Attempts to reunites the send and receive halves that form a WebSocket or returns an error if
 /// they do not represent the same connection.
+#[allow(clippy::result_large_err)]
 fn reunite<S, E>(
     sender: Sender<S, E::SplitEncoder>,
     receiver: Receiver<S, E::SplitDecoder>,
@@ -624,6 +1067,7 @@ where
         let Sender {
             split_writer: sender_writer,
             ext_encoder,
+            subprotocol,
             ..
         } = sender;
         let Receiver {
@@ -634,6 +1078,9 @@ where
         let FramedIo {
             flags,
             max_message_size,
+            first_message,
+            auto_pong,
+            pong_limit,
             read_half,
             reader,
             ext_decoder,
@@ -659,6 +1106,9 @@ where
             writer,
             flags,
             max_message_size,
+            first_message,
+            auto_pong,
+            pong_limit,
         });
 
         let close_state = match close_state.load(Ordering::SeqCst) {
@@ -673,6 +1123,7 @@ where
             control_buffer,
             NegotiatedExtension::reunite(ext_encoder, ext_decoder),
             close_state,
+            subprotocol,
         ))
     } else {
         Err(ReuniteError { sender, receiver })
@@ -0,0 +1,125 @@
+// Copyright 2015-2021 Swim Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::{Debug, Formatter};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use futures::future::BoxFuture;
+use futures::{FutureExt, Stream};
+use ratchet_ext::ExtensionDecoder;
+
+use crate::{Error, Message, Receiver, WebSocketStream};
+
+type ReadFuture<S, E> = BoxFuture<'static, (Receiver<S, E>, BytesMut, Result<Message, Error>)>;
+
+enum State<S, E> {
+    Idle(Receiver<S, E>, BytesMut),
+    Reading(ReadFuture<S, E>),
+    Terminated,
+}
+
+/// A `Stream` of the messages received by a `Receiver`.
+///
+/// Each item contains the message and its payload. Control frames are handled by the receiver in
+/// the same manner as `Receiver::read` and are yielded with their payload. The stream terminates
+/// once a close frame has been received or after an error has been yielded.
+pub struct ReceiverStream<S, E> {
+    state: State<S, E>,
+}
+
+// The state is never structurally pinned.
+impl<S, E> Unpin for ReceiverStream<S, E> {}
+
+impl<S, E> Debug for ReceiverStream<S, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = match &self.state {
+            State::Idle(..) => "Idle",
+            State::Reading(_) => "Reading",
+            State::Terminated => "Terminated",
+        };
+        f.debug_struct("ReceiverStream")
+            .field("state", &state)
+            .finish()
+    }
+}
+
+impl<S, E> ReceiverStream<S, E>
+where
+    S: WebSocketStream + Send,
+    E: ExtensionDecoder + Send + 'static,
+{
+    /// Constructs a new stream of the messages received by `receiver`.
+    pub fn new(receiver: Receiver<S, E>) -> ReceiverStream<S, E> {
+        ReceiverStream {
+            state: State::Idle(receiver, BytesMut::new()),
+        }
+    }
+}
+
+async fn read<S, E>(
+    mut receiver: Receiver<S, E>,
+    mut buf: BytesMut,
+) -> (Receiver<S, E>, BytesMut, Result<Message, Error>)
+where
+    S: WebSocketStream + Send,
+    E: ExtensionDecoder + Send + 'static,
+{
+    let result = receiver.read(&mut buf).await;
+    (receiver, buf, result)
+}
+
+impl<S, E> Stream for ReceiverStream<S, E>
+where
+    S: WebSocketStream + Send,
+    E: ExtensionDecoder + Send + 'static,
+{
+    type Item = Result<(Message, Bytes), Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match std::mem::replace(&mut this.state, State::Terminated) {
+                State::Idle(receiver, buf) => {
+                    this.state = State::Reading(read(receiver, buf).boxed());
+                }
+                State::Reading(mut future) => {
+                    let (receiver, mut buf, result) = match future.poll_unpin(cx) {
+                        Poll::Ready(output) => output,
+                        Poll::Pending => {
+                            this.state = State::Reading(future);
+                            return Poll::Pending;
+                        }
+                    };
+
+                    return match result {
+                        Ok(Message::Close(_)) => Poll::Ready(None),
+                        Ok(message) => {
+                            let payload = match &message {
+                                Message::Ping(payload) | Message::Pong(payload) => payload.clone(),
+                                _ => buf.split().freeze(),
+                            };
+                            this.state = State::Idle(receiver, buf);
+                            Poll::Ready(Some(Ok((message, payload))))
+                        }
+                        Err(e) => Poll::Ready(Some(Err(e))),
+                    };
+                }
+                State::Terminated => return Poll::Ready(None),
+            }
+        }
+    }
+}
@@ -17,12 +17,14 @@ use crate::protocol::{ControlCode, DataCode, HeaderFlags, OpCode};
 use crate::split::{FramedIo, Receiver, Sender, WriteHalf};
 use crate::ws::extension_encode;
 use crate::{
-    CloseCause, CloseCode, CloseReason, Error, Message, NegotiatedExtension, NoExt, NoExtDecoder,
-    NoExtEncoder, Role, WebSocket, WebSocketConfig, WebSocketStream,
+    CloseCause, CloseCode, CloseReason, Error, FragmentKind, Message, MessageType,
+    NegotiatedExtension, NoExt, NoExtDecoder, NoExtEncoder, PayloadType, ProtocolError, Role,
+    WebSocket, WebSocketConfig, WebSocketStream,
 };
 use bytes::{Bytes, BytesMut};
 use ratchet_ext::{ExtensionDecoder, ExtensionEncoder};
-use tokio::io::{duplex, DuplexStream};
+use std::time::Duration;
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
 use tokio::net::TcpStream;
 
 #[test]
@@ -38,7 +40,12 @@ where
     S: WebSocketStream,
     E: ExtensionEncoder,
 {
-    pub async fn write_frame<A>(&mut self, buf: A, opcode: OpCode, fin: bool) -> Result<(), Error>
+    pub(crate) async fn write_frame<A>(
+        &mut self,
+        buf: A,
+        opcode: OpCode,
+        fin: bool,
+    ) -> Result<(), Error>
     where
         A: AsRef<[u8]>,
     {
@@ -64,7 +71,6 @@ where
             |payload, header| extension_encode(ext_encoder, payload, header),
         )
         .await
-        .map_err(Into::into)
     }
 }
 
@@ -73,7 +79,8 @@ where
     S: WebSocketStream,
     E: ExtensionDecoder,
 {
-    pub async fn read_frame(&mut self, read_buffer: &mut BytesMut) -> Result<Item, Error> {
+    #[allow(dead_code)]
+    pub(crate) async fn read_frame(&mut self, read_buffer: &mut BytesMut) -> Result<Item, Error> {
         let Receiver { framed, .. } = self;
         let FramedIo {
             flags,
@@ -144,6 +151,31 @@ async fn ping_pong() {
     assert!(read_buf.is_empty());
 }
 
+#[tokio::test]
+async fn control_frames_not_queued_behind_data() {
+    let (server, mut peer) = duplex(4096);
+    let (mut server_tx, _server_rx) = WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        server,
+        NegotiatedExtension::from(NoExt),
+        BytesMut::new(),
+        Role::Server,
+    )
+    .split()
+    .unwrap();
+
+    for _ in 0..100 {
+        server_tx.write_binary("data").await.expect("Write failure");
+    }
+    server_tx.write_ping("ping").await.expect("Write failure");
+
+    // Every frame has been flushed by the time that the ping has been written.
+    let mut frames = vec![0; 100 * 6 + 6];
+    peer.read_exact(&mut frames).await.unwrap();
+    assert_eq!(frames[..6], [0x82, 0x04, b'd', b'a', b't', b'a']);
+    assert_eq!(frames[600..], [0x89, 0x04, b'p', b'i', b'n', b'g']);
+}
+
 #[tokio::test]
 async fn reads_unsolicited_pong() {
     let ((_client_tx, mut client_rx), (mut server_tx, _server_rx)) = fixture();
@@ -220,12 +252,73 @@ async fn interleaved_control_frames() {
     );
 }
 
+#[tokio::test]
+async fn read_fragments() {
+    let ((mut client_tx, mut client_rx), (_server_tx, mut server_rx)) = fixture();
+
+    client_tx
+        .write_frame("123", OpCode::DataCode(DataCode::Binary), false)
+        .await
+        .expect("Write failure");
+    client_tx
+        .write_frame("456", OpCode::DataCode(DataCode::Continuation), false)
+        .await
+        .expect("Write failure");
+    client_tx
+        .write_frame("data", OpCode::ControlCode(ControlCode::Ping), true)
+        .await
+        .expect("Write failure");
+    client_tx
+        .write_frame("789", OpCode::DataCode(DataCode::Continuation), true)
+        .await
+        .expect("Write failure");
+    client_tx.write_text("whole").await.expect("Write failure");
+
+    let expected = [
+        (FragmentKind::Binary, "123", false),
+        (FragmentKind::Continuation, "456", false),
+        (FragmentKind::Continuation, "789", true),
+    ];
+    for (kind, payload, fin) in expected {
+        let fragment = server_rx.read_fragment().await.expect("Read failure");
+        assert_eq!(fragment, (kind, Bytes::from(payload), fin));
+    }
+
+    // The ping that was interleaved between the fragments was responded to.
+    let mut buf = BytesMut::new();
+    let message = client_rx.read(&mut buf).await.expect("Read failure");
+    assert_eq!(message, Message::Pong(Bytes::from("data")));
+
+    let fragment = server_rx.read_fragment().await.expect("Read failure");
+    assert_eq!(fragment, (FragmentKind::Text, Bytes::from("whole"), true));
+}
+
+#[tokio::test]
+async fn byte_counts() {
+    let ((mut client_tx, client_rx), (_server_tx, mut server_rx)) = fixture();
+
+    client_tx.write_text("hello").await.expect("Write failure");
+    client_tx.write_ping("ping").await.expect("Write failure");
+    assert_eq!(client_tx.byte_counts(), (0, 21));
+    assert_eq!(client_rx.byte_counts(), (0, 21));
+
+    let mut buf = BytesMut::new();
+    server_rx.read(&mut buf).await.expect("Read failure");
+    server_rx.read(&mut buf).await.expect("Read failure");
+    // The ping was responded to by the receiver.
+    assert_eq!(server_rx.byte_counts(), (21, 6));
+}
+
 #[tokio::test]
 async fn large_control_frames() {
     {
         let ((mut client_tx, _client_rx), (_server_tx, _server_rx)) = fixture();
         let error = client_tx.write_ping(&[13; 256]).await.unwrap_err();
         assert!(error.is_protocol());
+        assert_eq!(
+            error.protocol_error(),
+            Some(&ProtocolError::ControlFrameOverflow)
+        );
     }
     {
         let ((_client_tx, mut client_rx), (mut server_tx, _server_rx)) = fixture();
@@ -236,6 +329,10 @@ async fn large_control_frames() {
 
         let error = client_rx.read(&mut BytesMut::new()).await.unwrap_err();
         assert!(error.is_protocol());
+        assert_eq!(
+            error.protocol_error(),
+            Some(&ProtocolError::ControlFrameOverflow)
+        );
     }
 }
 
@@ -270,6 +367,18 @@ async fn drop_end() {
     assert!(err.is_io());
 }
 
+#[tokio::test]
+async fn abnormal_close() {
+    let ((client_tx, client_rx), (_server_tx, mut server_rx)) = fixture();
+    drop(client_tx);
+    drop(client_rx);
+
+    let mut buf = BytesMut::new();
+    let err = server_rx.read(&mut buf).await.expect_err("Read failure");
+    assert!(err.is_abnormal_close());
+    assert!(!err.is_close());
+}
+
 #[tokio::test]
 async fn after_close() {
     let ((mut client_tx, mut client_rx), (_server_tx, mut server_rx)) = fixture();
@@ -387,6 +496,42 @@ async fn read_before_close() {
     assert!(err.is_close())
 }
 
+#[tokio::test]
+async fn read_while_closing() {
+    let ((mut client_tx, mut client_rx), (mut server_tx, mut server_rx)) = fixture();
+    let reason = CloseReason::new(CloseCode::Normal, None);
+
+    client_tx
+        .close(reason.clone())
+        .await
+        .expect("Close failure");
+    assert!(client_rx.is_closing());
+
+    // A data frame that the peer sent before it received the close frame.
+    server_tx.write_text("queued").await.expect("Write failure");
+
+    let mut buf = BytesMut::new();
+    let message = client_rx.read(&mut buf).await.expect("Read failure");
+    assert_eq!(message, Message::Text);
+    assert_eq!(buf.as_ref(), b"queued");
+    assert!(client_rx.is_closing());
+
+    let mut server_buf = BytesMut::new();
+    let message = server_rx.read(&mut server_buf).await.expect("Read failure");
+    assert_eq!(message, Message::Close(Some(reason)));
+
+    let err = client_rx
+        .read(&mut buf)
+        .await
+        .expect_err("Expected a close error");
+    assert_eq!(
+        err.downcast_ref::<CloseCause>().unwrap(),
+        &CloseCause::Stopped
+    );
+    assert!(!client_rx.is_closing());
+    assert!(client_rx.is_closed());
+}
+
 #[tokio::test]
 async fn close_then_err() {
     let ((mut client_tx, mut client_rx), (server_tx, server_rx)) = fixture();
@@ -440,3 +585,525 @@ async fn reuse_after_closure() {
         &CloseCause::Error
     );
 }
+
+#[tokio::test]
+async fn channel_bridge() {
+    let (server, client) = duplex(512);
+    let config = WebSocketConfig::default();
+
+    let server = WebSocket::from_upgraded(
+        config,
+        server,
+        NegotiatedExtension::from(NoExt),
+        BytesMut::new(),
+        Role::Server,
+    );
+    let mut client = WebSocket::from_upgraded(
+        config,
+        client,
+        NegotiatedExtension::from(NoExt),
+        BytesMut::new(),
+        Role::Client,
+    );
+
+    let (outbound, mut inbound, handle) = server.into_channels(4, 4);
+
+    client.write_text("hello").await.expect("Write failure");
+    client.write_ping("ping").await.expect("Write failure");
+    client.write_binary("world").await.expect("Write failure");
+
+    assert_eq!(
+        inbound.recv().await,
+        Some((Message::Text, Bytes::from("hello")))
+    );
+    assert_eq!(
+        inbound.recv().await,
+        Some((Message::Binary, Bytes::from("world")))
+    );
+
+    outbound
+        .send((PayloadType::Text, Bytes::from("echo")))
+        .await
+        .expect("Send failure");
+
+    let mut buf = BytesMut::new();
+    let message = client.read(&mut buf).await.expect("Read failure");
+    assert_eq!(message, Message::Pong(Bytes::from("ping")));
+    let message = client.read(&mut buf).await.expect("Read failure");
+    assert_eq!(message, Message::Text);
+    assert_eq!(buf.as_ref(), b"echo");
+
+    drop(outbound);
+
+    let message = client.read(&mut buf).await.expect("Read failure");
+    assert_eq!(
+        message,
+        Message::Close(Some(CloseReason::new(CloseCode::Normal, None)))
+    );
+
+    handle.await.unwrap().expect("Bridge failure");
+    assert!(inbound.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn receiver_stream() {
+    use futures::StreamExt;
+
+    let ((mut client_tx, _client_rx), (_server_tx, server_rx)) = fixture();
+    let mut stream = server_rx.into_stream();
+
+    client_tx.write_text("hello").await.expect("Write failure");
+    client_tx
+        .write_binary("world")
+        .await
+        .expect("Write failure");
+    client_tx
+        .close(CloseReason::new(CloseCode::Normal, None))
+        .await
+        .expect("Write failure");
+
+    let (message, payload) = stream.next().await.unwrap().expect("Read failure");
+    assert_eq!(message, Message::Text);
+    assert_eq!(payload, Bytes::from("hello"));
+
+    let (message, payload) = stream.next().await.unwrap().expect("Read failure");
+    assert_eq!(message, Message::Binary);
+    assert_eq!(payload, Bytes::from("world"));
+
+    assert!(stream.next().await.is_none());
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn poll_receiver_stream() {
+    use futures::future::poll_fn;
+    use futures::Stream;
+    use std::pin::Pin;
+    use std::task::Poll;
+
+    let ((mut client_tx, _client_rx), (_server_tx, server_rx)) = fixture();
+    let mut stream = server_rx.into_stream();
+
+    let poll = poll_fn(|cx| Poll::Ready(Pin::new(&mut stream).poll_next(cx))).await;
+    assert!(poll.is_pending());
+
+    client_tx
+        .write_fragmented("hello world", MessageType::Text, 6)
+        .await
+        .expect("Write failure");
+
+    let (message, payload) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+        .await
+        .unwrap()
+        .expect("Read failure");
+
+    assert_eq!(message, Message::Text);
+    assert_eq!(payload, Bytes::from("hello world"));
+}
+
+#[tokio::test]
+async fn poll_read() {
+    use futures::future::poll_fn;
+    use std::task::Poll;
+
+    let ((mut client_tx, _client_rx), (_server_tx, mut server_rx)) = fixture();
+    let mut buf = BytesMut::new();
+
+    let poll = poll_fn(|cx| Poll::Ready(server_rx.poll_read(cx, &mut buf))).await;
+    assert!(poll.is_pending());
+
+    client_tx
+        .write_frame("hello ", OpCode::DataCode(DataCode::Text), false)
+        .await
+        .expect("Write failure");
+
+    let poll = poll_fn(|cx| Poll::Ready(server_rx.poll_read(cx, &mut buf))).await;
+    assert!(poll.is_pending());
+
+    client_tx
+        .write_frame("world", OpCode::DataCode(DataCode::Continuation), true)
+        .await
+        .expect("Write failure");
+
+    let message = poll_fn(|cx| server_rx.poll_read(cx, &mut buf))
+        .await
+        .expect("Read failure");
+
+    assert_eq!(message, Message::Text);
+    assert_eq!(buf, BytesMut::from("hello world"));
+}
+
+#[tokio::test]
+async fn toggle_auto_pong() {
+    let ((mut client_tx, mut client_rx), (mut server_tx, mut server_rx)) = fixture();
+    let mut buf = BytesMut::new();
+
+    server_rx.set_auto_pong(false);
+    client_tx.write_ping("1").await.expect("Write failure");
+
+    let message = server_rx.read(&mut buf).await.expect("Read failure");
+    assert_eq!(message, Message::Ping(Bytes::from("1")));
+
+    // If a pong had been sent then the client would read it before this frame
+    server_tx.write_text("text").await.expect("Write failure");
+    let message = client_rx.read(&mut buf).await.expect("Read failure");
+    assert_eq!(message, Message::Text);
+    assert_eq!(buf.as_ref(), b"text");
+    buf.clear();
+
+    server_rx.set_auto_pong(true);
+    client_tx.write_ping("2").await.expect("Write failure");
+
+    let message = server_rx.read(&mut buf).await.expect("Read failure");
+    assert_eq!(message, Message::Ping(Bytes::from("2")));
+
+    let message = client_rx.read(&mut buf).await.expect("Read failure");
+    assert_eq!(message, Message::Pong(Bytes::from("2")));
+}
+
+#[tokio::test]
+async fn write_vectored() {
+    let ((mut client_tx, _client_rx), (_server_tx, mut server_rx)) = fixture();
+    let mut buf = BytesMut::new();
+
+    client_tx
+        .write_vectored(&[b"one", b"", b"two", b"three"], MessageType::Binary)
+        .await
+        .expect("Write failure");
+
+    let message = server_rx.read(&mut buf).await.expect("Read failure");
+    assert_eq!(message, Message::Binary);
+    assert_eq!(buf.as_ref(), b"onetwothree");
+}
+
+#[tokio::test]
+async fn write_vectored_unmasked() {
+    let (server, client) = duplex(512);
+    let config = WebSocketConfig::default();
+
+    let (mut server_tx, _server_rx) = WebSocket::from_upgraded(
+        config,
+        server,
+        NegotiatedExtension::from(None::<NoExt>),
+        BytesMut::new(),
+        Role::Server,
+    )
+    .split()
+    .unwrap();
+    let mut client = WebSocket::from_upgraded(
+        config,
+        client,
+        NegotiatedExtension::from(None::<NoExt>),
+        BytesMut::new(),
+        Role::Client,
+    );
+
+    server_tx
+        .write_vectored(&[b"one", b"two", b"three"], MessageType::Text)
+        .await
+        .expect("Write failure");
+
+    let mut buf = BytesMut::new();
+    let message = client.read(&mut buf).await.expect("Read failure");
+    assert_eq!(message, Message::Text);
+    assert_eq!(buf.as_ref(), b"onetwothree");
+}
+
+#[tokio::test]
+async fn max_outgoing_frame_size() {
+    let (server, client) = duplex(512);
+    let config = WebSocketConfig {
+        max_outgoing_frame_size: Some(1024),
+        ..Default::default()
+    };
+
+    let (_server_tx, mut server_rx) = WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        server,
+        NegotiatedExtension::from(NoExt),
+        BytesMut::new(),
+        Role::Server,
+    )
+    .split()
+    .unwrap();
+    let (mut client_tx, _client_rx) = WebSocket::from_upgraded(
+        config,
+        client,
+        NegotiatedExtension::from(NoExt),
+        BytesMut::new(),
+        Role::Client,
+    )
+    .split()
+    .unwrap();
+
+    let payload = (0..10 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+    let write = async {
+        client_tx
+            .write_binary(&payload)
+            .await
+            .expect("Write failure");
+        client_tx.write_text("small").await.expect("Write failure");
+    };
+    let read = async {
+        let mut received = Vec::new();
+        for i in 0..10 {
+            let (kind, fragment, fin) = server_rx.read_fragment().await.expect("Read failure");
+            let expected_kind = if i == 0 {
+                FragmentKind::Binary
+            } else {
+                FragmentKind::Continuation
+            };
+            assert_eq!(kind, expected_kind);
+            assert_eq!(fragment.len(), 1024);
+            assert_eq!(fin, i == 9);
+            received.extend_from_slice(&fragment);
+        }
+        assert_eq!(received, payload);
+
+        let fragment = server_rx.read_fragment().await.expect("Read failure");
+        assert_eq!(fragment, (FragmentKind::Text, Bytes::from("small"), true));
+    };
+
+    tokio::join!(write, read);
+}
+
+#[tokio::test]
+async fn byte_stream_round_trip() {
+    use crate::WebSocketByteStream;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (server, client) = duplex(4096);
+    let config = WebSocketConfig::default();
+    let server = WebSocket::from_upgraded(
+        config,
+        server,
+        NegotiatedExtension::from(NoExt),
+        BytesMut::new(),
+        Role::Server,
+    );
+    let client = WebSocket::from_upgraded(
+        config,
+        client,
+        NegotiatedExtension::from(NoExt),
+        BytesMut::new(),
+        Role::Client,
+    );
+
+    let server = WebSocketByteStream::new(server).unwrap();
+    let client = WebSocketByteStream::new(client).unwrap();
+
+    // The server echoes everything that it receives until the client shuts down.
+    let echo = tokio::spawn(async move {
+        let (mut rx, mut tx) = tokio::io::split(server);
+        let count = tokio::io::copy(&mut rx, &mut tx).await.unwrap();
+        tx.shutdown().await.unwrap();
+        count
+    });
+
+    let payload = (0..64 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+    let (mut rx, mut tx) = tokio::io::split(client);
+    let write = async {
+        for chunk in payload.chunks(5000) {
+            tx.write_all(chunk).await.unwrap();
+        }
+        tx.flush().await.unwrap();
+    };
+    let read = async {
+        let mut received = vec![0; payload.len()];
+        rx.read_exact(&mut received).await.unwrap();
+        received
+    };
+
+    let ((), received) = tokio::join!(write, read);
+    assert_eq!(received, payload);
+
+    tx.shutdown().await.unwrap();
+    let mut rest = Vec::new();
+    rx.read_to_end(&mut rest).await.unwrap();
+    assert!(rest.is_empty());
+
+    assert_eq!(echo.await.unwrap(), payload.len() as u64);
+}
+
+#[tokio::test]
+async fn read_fragment_unexpected_continuation() {
+    let ((mut client_tx, _client_rx), (_server_tx, mut server_rx)) = fixture();
+
+    client_tx
+        .write_frame("123", OpCode::DataCode(DataCode::Continuation), true)
+        .await
+        .expect("Write failure");
+
+    let error = server_rx.read_fragment().await.unwrap_err();
+    assert!(error.is_protocol());
+    assert_eq!(
+        error.downcast_ref::<ProtocolError>(),
+        Some(&ProtocolError::ContinuationNotStarted)
+    );
+}
+
+#[tokio::test]
+async fn read_fragment_new_message_while_fragmented() {
+    let ((mut client_tx, _client_rx), (_server_tx, mut server_rx)) = fixture();
+
+    client_tx
+        .write_frame("123", OpCode::DataCode(DataCode::Binary), false)
+        .await
+        .expect("Write failure");
+    client_tx
+        .write_frame("456", OpCode::DataCode(DataCode::Text), true)
+        .await
+        .expect("Write failure");
+
+    let fragment = server_rx.read_fragment().await.expect("Read failure");
+    assert_eq!(fragment, (FragmentKind::Binary, Bytes::from("123"), false));
+
+    let error = server_rx.read_fragment().await.unwrap_err();
+    assert!(error.is_protocol());
+    assert_eq!(
+        error.downcast_ref::<ProtocolError>(),
+        Some(&ProtocolError::ContinuationAlreadyStarted)
+    );
+}
+
+#[tokio::test]
+async fn unexpected_first_opcode_no_close() {
+    for fragment in [false, true] {
+        let (server, mut peer) = duplex(512);
+        let server = WebSocket::from_upgraded(
+            WebSocketConfig {
+                expected_first_opcode: Some(MessageType::Text),
+                close_on_unexpected_opcode: false,
+                ..Default::default()
+            },
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
+        let (sender, mut receiver) = server.split().unwrap();
+
+        // A masked binary frame with a zeroed masking key.
+        peer.write_all(&[0x82, 0x82, 0x00, 0x00, 0x00, 0x00, b'h', b'i'])
+            .await
+            .expect("Write failure");
+
+        let error = if fragment {
+            receiver.read_fragment().await.unwrap_err()
+        } else {
+            receiver.read(&mut BytesMut::new()).await.unwrap_err()
+        };
+        assert_eq!(
+            error.downcast_ref::<ProtocolError>(),
+            Some(&ProtocolError::UnexpectedOpCode)
+        );
+        assert!(receiver.is_closed());
+        assert!(sender.is_closed());
+
+        // The server shut down the stream without writing a close frame.
+        drop((sender, receiver));
+        let mut written = Vec::new();
+        peer.read_to_end(&mut written).await.expect("Read failure");
+        assert!(written.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn drain_until_close() {
+    let ((mut client_tx, mut client_rx), (mut server_tx, mut server_rx)) = fixture();
+    let reason = CloseReason::new(CloseCode::GoingAway, Some("Shutting down".to_string()));
+
+    server_tx
+        .close(CloseReason::new(CloseCode::Normal, None))
+        .await
+        .expect("Close failure");
+
+    // Data frames that the peer sent before it received the close frame.
+    client_tx.write_text("first").await.expect("Write failure");
+    client_tx
+        .write_binary("second")
+        .await
+        .expect("Write failure");
+    client_tx
+        .close(reason.clone())
+        .await
+        .expect("Close failure");
+
+    let received = server_rx
+        .drain_until_close(Duration::from_secs(5))
+        .await
+        .expect("Drain failure");
+    assert_eq!(received, Some(reason));
+    assert!(server_rx.is_closed());
+
+    // The peer's closing handshake is completed by the server's close frame.
+    let mut buf = BytesMut::new();
+    let err = client_rx
+        .read(&mut buf)
+        .await
+        .expect_err("Expected a close error");
+    assert_eq!(
+        err.downcast_ref::<CloseCause>().unwrap(),
+        &CloseCause::Stopped
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn drain_until_close_timeout() {
+    let ((_client_tx, _client_rx), (mut server_tx, mut server_rx)) = fixture();
+
+    server_tx
+        .close(CloseReason::new(CloseCode::Normal, None))
+        .await
+        .expect("Close failure");
+
+    let err = server_rx
+        .drain_until_close(Duration::from_secs(5))
+        .await
+        .expect_err("Expected a timeout");
+    assert!(err.is_timeout());
+    assert!(server_rx.is_closing());
+}
+
+#[tokio::test]
+async fn close_does_not_await_echo() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (server, mut peer) = duplex(512);
+    let (mut server_tx, mut server_rx) = WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        server,
+        NegotiatedExtension::from(NoExt),
+        BytesMut::new(),
+        Role::Server,
+    )
+    .split()
+    .unwrap();
+
+    // The peer has not read anything, so this would not complete if it awaited the echo.
+    server_tx
+        .close(CloseReason::new(
+            CloseCode::GoingAway,
+            Some("bye".to_string()),
+        ))
+        .await
+        .expect("Close failure");
+    assert!(server_tx.is_closing());
+
+    let mut frame = [0; 7];
+    peer.read_exact(&mut frame).await.expect("Read failure");
+    assert_eq!(frame, [0x88, 0x05, 0x03, 0xe9, b'b', b'y', b'e']);
+
+    // The stream has not been shut down and so the closing handshake may still be completed.
+    peer.write_all(&[0x88, 0x82, 0, 0, 0, 0, 0x03, 0xe9])
+        .await
+        .expect("Write failure");
+    let err = server_rx
+        .read(&mut BytesMut::new())
+        .await
+        .expect_err("Expected a close error");
+    assert_eq!(
+        err.downcast_ref::<CloseCause>().unwrap(),
+        &CloseCause::Stopped
+    );
+}
@@ -14,22 +14,27 @@
 
 use crate::errors::{CloseCause, Error, ErrorKind, ProtocolError};
 use crate::ext::NegotiatedExtension;
-use crate::framed::{FramedIo, Item};
+use crate::framed::{FirstMessage, FramedIo, Item, PongLimit};
 use crate::protocol::{
-    CloseReason, ControlCode, DataCode, HeaderFlags, Message, MessageType, OpCode, PayloadType,
-    Role,
+    CloseCode, CloseReason, ControlCode, DataCode, FragmentKind, FrameConstraints, HeaderFlags,
+    MaskingKeyGen, Message, MessageType, OpCode, PayloadType, Role,
 };
-use crate::{WebSocketConfig, WebSocketStream};
-use bytes::BytesMut;
+use crate::{BufferPool, HasPeerAddr, WebSocketConfig, WebSocketStream};
+use bytes::{Bytes, BytesMut};
 use log::{error, trace};
-use ratchet_ext::{Extension, ExtensionEncoder, FrameHeader as ExtFrameHeader};
+use ratchet_ext::{
+    Extension, ExtensionEncoder, ExtensionState, ExtensionStats, FrameHeader as ExtFrameHeader,
+};
+use std::io;
+use std::net::SocketAddr;
 
 #[cfg(feature = "split")]
-use crate::split::{split, Receiver, Sender};
+use crate::split::{bridge, split, ChannelBridge, Receiver, Sender};
 #[cfg(feature = "split")]
 use ratchet_ext::SplittableExtension;
 
 pub const CONTROL_MAX_SIZE: usize = 125;
+const EXTENSIONS_NONE: &str = "none";
 
 #[cfg(feature = "split")]
 type SplitSocket<S, E> = (
@@ -79,6 +84,7 @@ pub struct WebSocket<S, E> {
     control_buffer: BytesMut,
     extension: NegotiatedExtension<E>,
     close_state: CloseState,
+    subprotocol: Option<String>,
 }
 
 /// Denotes the current state of a WebSocket session.
@@ -103,12 +109,14 @@ where
         control_buffer: BytesMut,
         extension: NegotiatedExtension<E>,
         close_state: CloseState,
+        subprotocol: Option<String>,
     ) -> WebSocket<S, E> {
         WebSocket {
             framed,
             control_buffer,
             extension,
             close_state,
+            subprotocol,
         }
     }
 
@@ -128,21 +136,133 @@ where
         read_buffer: BytesMut,
         role: Role,
     ) -> WebSocket<S, E> {
-        let WebSocketConfig { max_message_size } = config;
+        let WebSocketConfig {
+            max_message_size,
+            expected_first_opcode,
+            close_on_unexpected_opcode,
+            auto_pong,
+            min_auto_pong_interval,
+            accept_unmasked_frames,
+            // Only applies to the handshake.
+            handshake_timeout: _,
+            capture_handshake: _,
+            max_outgoing_frame_size,
+            write_timeout,
+            validate_utf8,
+            max_fragments,
+        } = config;
+        let mut framed = FramedIo::new(
+            stream,
+            read_buffer,
+            role,
+            max_message_size,
+            extension.bits().into(),
+        );
+        framed.set_first_message(FirstMessage::new(
+            role,
+            expected_first_opcode,
+            close_on_unexpected_opcode,
+        ));
+        framed.set_auto_pong(auto_pong);
+        framed.set_pong_limit(PongLimit::new(min_auto_pong_interval));
+        framed.set_accept_unmasked(accept_unmasked_frames);
+        framed.set_validate_utf8(validate_utf8);
+        framed.set_max_outgoing_frame_size(max_outgoing_frame_size);
+        framed.set_write_timeout(write_timeout);
+        framed.set_max_fragments(max_fragments);
+
         WebSocket {
-            framed: FramedIo::new(
-                stream,
-                read_buffer,
-                role,
-                max_message_size,
-                extension.bits().into(),
-            ),
+            framed,
             extension,
             control_buffer: BytesMut::with_capacity(CONTROL_MAX_SIZE),
             close_state: CloseState::NotClosed,
+            subprotocol: None,
         }
     }
 
+    /// Initialise a new `WebSocket` from a stream that has been upgraded by another HTTP
+    /// implementation, such as `hyper`. No handshake is performed and the results of the
+    /// negotiation that the caller performed are trusted.
+    ///
+    /// # Arguments
+    /// `config` - The configuration to initialise the WebSocket with.
+    /// `stream` - The stream that was upgraded.
+    /// `subprotocol` - The subprotocol that was selected during the upgrade, if any.
+    /// `extension` - The extension that was negotiated during the upgrade.
+    /// `read_buffer` - Any bytes that were read from the stream after the upgrade request but
+    /// were not consumed by the HTTP implementation.
+    /// `role` - The role that this WebSocket will take.
+    pub fn from_http_upgrade(
+        config: WebSocketConfig,
+        stream: S,
+        subprotocol: Option<String>,
+        extension: NegotiatedExtension<E>,
+        read_buffer: BytesMut,
+        role: Role,
+    ) -> WebSocket<S, E> {
+        WebSocket::from_upgraded(config, stream, extension, read_buffer, role)
+            .with_subprotocol(subprotocol)
+    }
+
+    pub(crate) fn with_subprotocol(mut self, subprotocol: Option<String>) -> WebSocket<S, E> {
+        self.subprotocol = subprotocol;
+        self
+    }
+
+    /// Returns the subprotocol that was negotiated for this session, if any.
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.subprotocol.as_deref()
+    }
+
+    /// Initialise a new `WebSocket` from a stream that has already executed a handshake and an
+    /// extension whose state is restored from `state`. This allows an active session, such as one
+    /// that is using compression, to be handed off from another process.
+    ///
+    /// # Arguments
+    /// `config` - The configuration to initialise the WebSocket with.
+    /// `stream` - The stream that the handshake was executed on.
+    /// `extension` - A newly negotiated extension, with the same parameters as the extension that
+    /// `state` was saved from.
+    /// `state` - The state that was saved from the extension of the original session.
+    /// `read_buffer` - The read buffer which will be used for the session. This **may** contain any
+    /// unread data received after performing the handshake that was not required.
+    /// `role` - The role that this WebSocket will take.
+    ///
+    /// # Errors
+    /// Errors with an extension error if the state could not be restored.
+    pub fn from_upgraded_with_extension_state(
+        config: WebSocketConfig,
+        stream: S,
+        mut extension: E,
+        state: E::State,
+        read_buffer: BytesMut,
+        role: Role,
+    ) -> Result<WebSocket<S, E>, Error>
+    where
+        E: ExtensionState,
+    {
+        extension
+            .restore(state)
+            .map_err(|e| Error::with_cause(ErrorKind::Extension, e))?;
+        Ok(WebSocket::from_upgraded(
+            config,
+            stream,
+            NegotiatedExtension::from(extension),
+            read_buffer,
+            role,
+        ))
+    }
+
+    /// Returns the state of the negotiated extension, if one was negotiated and it has retained its
+    /// state, so that it may be restored into another session using
+    /// `from_upgraded_with_extension_state`.
+    pub fn extension_state(&self) -> Option<E::State>
+    where
+        E: ExtensionState,
+    {
+        self.extension.state()
+    }
+
     /// Returns the role of this WebSocket.
     pub fn role(&self) -> Role {
         if self.framed.is_server() {
@@ -152,6 +272,82 @@ where
         }
     }
 
+    /// Returns the constraints that this WebSocket's role and negotiated extension impose on the
+    /// frames that it sends.
+    pub fn constraints(&self) -> FrameConstraints {
+        FrameConstraints {
+            must_mask: !self.framed.is_server(),
+            max_control_size: CONTROL_MAX_SIZE,
+            rsv_bits: self.extension.bits().into(),
+        }
+    }
+
+    /// Returns the number of raw bytes that have been `(read, written)` by this WebSocket. This
+    /// includes frame headers and masks and is counted after any extension has encoded the
+    /// payload, unlike `extension_stats`.
+    pub fn byte_counts(&self) -> (u64, u64) {
+        self.framed.byte_counts()
+    }
+
+    /// Returns the statistics recorded by the negotiated extension, if one was negotiated and it
+    /// records any.
+    pub fn extension_stats(&self) -> Option<ExtensionStats> {
+        self.extension.stats()
+    }
+
+    /// Resets the statistics recorded by the negotiated extension.
+    pub fn reset_extension_stats(&mut self) {
+        self.extension.reset_stats();
+    }
+
+    /// Returns a concise description of the negotiated extension and its parameters for logging,
+    /// such as `permessage-deflate; server_max_window_bits=15; client_max_window_bits=15`.
+    /// Returns `none` if no extension was negotiated or if the extension does not describe itself.
+    pub fn extensions_summary(&self) -> String {
+        self.extension
+            .summary()
+            .unwrap_or_else(|| EXTENSIONS_NONE.to_string())
+    }
+
+    /// Sets whether to automatically respond to any ping frames that are received with a pong
+    /// frame. If disabled, received ping frames are still returned by `read` as `Message::Ping`.
+    pub fn set_auto_pong(&mut self, auto_pong: bool) {
+        self.framed.set_auto_pong(auto_pong);
+    }
+
+    /// Sets the pool that buffers for encoding the frames that are written are taken from, or
+    /// `None` to allocate a new buffer for each frame. Frames that are read do not use the pool.
+    /// The pool is retained by both halves if this WebSocket is split.
+    pub fn set_buffer_pool(&mut self, pool: Option<BufferPool>) {
+        self.framed.set_buffer_pool(pool);
+    }
+
+    /// Sets the maximum size of messages that may be received. The new limit applies from the
+    /// next message that is read; a fragmented message that is partially read is still bounded
+    /// by the limit that was in effect when it started. A size of zero disables the limit.
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.framed.set_max_message_size(max_message_size);
+    }
+
+    /// Returns whether the RSV1 bit was set on the most recently read data message. When the
+    /// permessage-deflate extension has been negotiated, this indicates that the message was
+    /// compressed by the peer.
+    pub fn last_message_compressed(&self) -> bool {
+        self.framed.last_message_compressed()
+    }
+
+    /// Sets the generator that is used for producing the masking keys of frames that this
+    /// WebSocket sends.
+    ///
+    /// Only frames sent by a client are masked and so this has no effect if this WebSocket is a
+    /// server. The generator is retained if this WebSocket is split.
+    pub fn set_masking_key_gen<G>(&mut self, key_gen: G)
+    where
+        G: MaskingKeyGen,
+    {
+        self.framed.set_masking_key_gen(key_gen);
+    }
+
     /// Attempt to read some data from the WebSocket. Returning either the type of the message
     /// received or the error that was produced.
     ///
@@ -171,6 +367,17 @@ where
     /// continuation, this function will then yield `Message::Ping` and the `read_buffer` will
     /// contain the data received up to that point. The callee must ensure that the contents of
     /// `read_buffer` are **not** then modified before calling `read` again.
+    ///
+    /// If a close frame is received while reading a continuation, the data received up to that
+    /// point is removed from `read_buffer` and `Message::Close` is returned.
+    ///
+    /// # Cancel safety
+    /// This method is cancellation safe while it is waiting for data, so it may be used as a
+    /// branch of `tokio::select!`. Any partially received frame is retained by the WebSocket and
+    /// the next call continues from where the dropped one stopped, provided that the same
+    /// `read_buffer` is passed to it unmodified. If the future is dropped while an automatic pong
+    /// or a close frame is being written in response to a received frame then the response may
+    /// be partially written.
     pub async fn read(&mut self, read_buffer: &mut BytesMut) -> Result<Message, Error> {
         if self.is_closed() {
             return Err(Error::with_cause(ErrorKind::Close, CloseCause::Error));
@@ -186,19 +393,30 @@ where
 
         match framed.read_next(read_buffer, extension).await {
             Ok(item) => match item {
-                Item::Binary => Ok(Message::Binary),
-                Item::Text => Ok(Message::Text),
+                Item::Binary => match framed.first_message().check(MessageType::Binary) {
+                    Ok(()) => Ok(Message::Binary),
+                    Err(e) => fail_first_message(close_state, framed, e).await,
+                },
+                Item::Text => match framed.first_message().check(MessageType::Text) {
+                    Ok(()) => Ok(Message::Text),
+                    Err(e) => fail_first_message(close_state, framed, e).await,
+                },
                 Item::Ping(payload) => {
-                    trace!("Received a ping frame. Responding with pong");
                     let ret = payload.clone().freeze();
-                    framed
-                        .write(
-                            OpCode::ControlCode(ControlCode::Pong),
-                            HeaderFlags::FIN,
-                            payload,
-                            |_, _| Ok(()),
-                        )
-                        .await?;
+                    if framed.should_pong() {
+                        trace!("Received a ping frame. Responding with pong");
+                        let result = framed
+                            .write(
+                                OpCode::ControlCode(ControlCode::Pong),
+                                HeaderFlags::FIN,
+                                payload,
+                                |_, _| Ok(()),
+                            )
+                            .await;
+                        fail_on_timeout(close_state, result)?;
+                    } else {
+                        trace!("Received a ping frame");
+                    }
                     Ok(Message::Ping(ret))
                 }
                 Item::Pong(payload) => {
@@ -236,6 +454,10 @@ where
     }
 
     /// Constructs a new ping WebSocket message with a payload of `data`.
+    ///
+    /// # Errors
+    /// Errors with a `ProtocolError::ControlFrameOverflow` if `data` is longer than 125 bytes, the
+    /// maximum payload size of a control frame.
     pub async fn write_ping<I>(&mut self, data: I) -> Result<(), Error>
     where
         I: AsRef<[u8]>,
@@ -262,6 +484,13 @@ where
 
         let buf = buf.as_ref();
 
+        if let (Some(data_type), Some(fragment_size)) = (
+            message_type.data_type(),
+            self.framed.fragment_size(buf.len()),
+        ) {
+            return self.write_fragmented(buf, data_type, fragment_size).await;
+        }
+
         let op_code = match message_type {
             PayloadType::Text => OpCode::DataCode(DataCode::Text),
             PayloadType::Binary => OpCode::DataCode(DataCode::Binary),
@@ -269,7 +498,7 @@ where
                 if buf.len() > CONTROL_MAX_SIZE {
                     return Err(Error::with_cause(
                         ErrorKind::Protocol,
-                        ProtocolError::FrameOverflow,
+                        ProtocolError::ControlFrameOverflow,
                     ));
                 } else {
                     self.control_buffer.clear();
@@ -281,7 +510,7 @@ where
                 if buf.len() > CONTROL_MAX_SIZE {
                     return Err(Error::with_cause(
                         ErrorKind::Protocol,
-                        ProtocolError::FrameOverflow,
+                        ProtocolError::ControlFrameOverflow,
                     ));
                 } else {
                     OpCode::ControlCode(ControlCode::Pong)
@@ -290,11 +519,71 @@ where
         };
 
         let encoder = &mut self.extension;
-        self.framed
+        let result = self
+            .framed
             .write(op_code, HeaderFlags::FIN, buf, |payload, header| {
                 extension_encode(encoder, payload, header)
             })
-            .await
+            .await;
+        fail_on_timeout(&mut self.close_state, result)
+    }
+
+    /// Writes a data frame of `kind` with a payload of `payload`. If `fin` is set then the frame is
+    /// the final frame of its message. A message that is started by a frame without `fin` set must
+    /// be continued by frames of `FragmentKind::Continuation`, the last of which has `fin` set.
+    ///
+    /// When this WebSocket is a server and no extension has been negotiated, the frame is written
+    /// without copying `payload`: the frame header and the payload are written using a vectored
+    /// write. Otherwise, the payload must be copied so that it can be masked or encoded by the
+    /// extension.
+    ///
+    /// A complete message, of `FragmentKind::Text` or `FragmentKind::Binary` with `fin` set, that
+    /// exceeds `WebSocketConfig::max_outgoing_frame_size` is fragmented in the same manner as
+    /// `write`.
+    pub async fn write_frame_bytes(
+        &mut self,
+        payload: Bytes,
+        kind: FragmentKind,
+        fin: bool,
+    ) -> Result<(), Error> {
+        if !self.is_active() {
+            return Err(Error::with_cause(ErrorKind::Close, CloseCause::Error));
+        }
+
+        let (op_code, message_type) = match kind {
+            FragmentKind::Text => (DataCode::Text, Some(MessageType::Text)),
+            FragmentKind::Binary => (DataCode::Binary, Some(MessageType::Binary)),
+            FragmentKind::Continuation => (DataCode::Continuation, None),
+        };
+
+        if let (true, Some(message_type), Some(fragment_size)) =
+            (fin, message_type, self.framed.fragment_size(payload.len()))
+        {
+            return self
+                .write_fragmented(payload, message_type, fragment_size)
+                .await;
+        }
+
+        let op_code = OpCode::DataCode(op_code);
+        let header_flags = if fin {
+            HeaderFlags::FIN
+        } else {
+            HeaderFlags::empty()
+        };
+
+        let result = if self.framed.is_server() && !self.extension.is_negotiated() {
+            self.framed
+                .write_unmasked(op_code, header_flags, payload)
+                .await
+        } else {
+            let encoder = &mut self.extension;
+            self.framed
+                .write(op_code, header_flags, payload, |payload, header| {
+                    extension_encode(encoder, payload, header)
+                })
+                .await
+        };
+        fail_on_timeout(&mut self.close_state, result)
     }
 
     /// Close this WebSocket with the reason provided.
@@ -306,7 +595,18 @@ where
         }
 
         self.close_state = CloseState::Closing;
-        self.framed.write_close(reason).await
+        let result = self.framed.write_close(reason).await;
+        fail_on_timeout(&mut self.close_state, result)
+    }
+
+    /// Close this WebSocket with a going away (1001) close code. Typically used when a server is
+    /// shutting down.
+    ///
+    /// This initiates the closing handshake in the same manner as `close` and so the WebSocket
+    /// should continue to be read from until the peer has echoed the close frame.
+    pub async fn going_away(&mut self) -> Result<(), Error> {
+        self.close(CloseReason::new(CloseCode::GoingAway, None))
+            .await
     }
 
     /// Constructs a new WebSocket message of `message_type` and with a payload of `buf_ref` and
@@ -326,11 +626,13 @@ where
         }
 
         let encoder = &mut self.extension;
-        self.framed
+        let result = self
+            .framed
             .write_fragmented(buf, message_type, fragment_size, |payload, header| {
                 extension_encode(encoder, payload, header)
             })
-            .await
+            .await;
+        fail_on_timeout(&mut self.close_state, result)
     }
 
     /// Returns whether this WebSocket is closed.
@@ -368,9 +670,79 @@ where
                 framed,
                 control_buffer,
                 extension,
+                subprotocol,
                 ..
             } = self;
-            Ok(split(framed, control_buffer, extension))
+            Ok(split(framed, control_buffer, extension, subprotocol))
+        }
+    }
+
+    /// Bridges this `WebSocket` to a pair of bounded channels, spawning a task that drives the
+    /// socket. Returns a sender for writing messages to the peer, a receiver of the text, binary
+    /// and close messages received from the peer and a handle to the spawned task.
+    ///
+    /// Control frames are handled by the spawned task. Dropping the returned sender closes the
+    /// WebSocket with a normal close code and the task completes once the closing handshake has
+    /// completed. If the inbound channel is full then no further reads will be made from the
+    /// socket until capacity is available.
+    ///
+    /// # Panics
+    /// Panics if either capacity is zero or if this is called outside of a Tokio runtime.
+    #[cfg(feature = "split")]
+    pub fn into_channels(self, inbound_capacity: usize, outbound_capacity: usize) -> ChannelBridge
+    where
+        S: Send,
+        E: SplittableExtension + Send + 'static,
+    {
+        bridge(self, inbound_capacity, outbound_capacity)
+    }
+}
+
+impl<S, E> WebSocket<S, E>
+where
+    S: WebSocketStream + HasPeerAddr,
+    E: Extension,
+{
+    /// Returns the address of the remote peer of the underlying stream.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.framed.io().peer_addr()
+    }
+
+    /// Returns the local address of the underlying stream.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.framed.io().local_addr()
+    }
+}
+
+/// Moves `close_state` to closed if `result` is a write timeout. The frame that was being written
+/// may have been partially written and so the connection may not be written to again.
+fn fail_on_timeout<T>(close_state: &mut CloseState, result: Result<T, Error>) -> Result<T, Error> {
+    if matches!(&result, Err(e) if e.is_timeout()) {
+        *close_state = CloseState::Closed;
+    }
+    result
+}
+
+/// Fails the connection after the first data message that was received did not match the
+/// expected type. A policy violation close frame is sent if `close_on_unexpected_opcode` is set,
+/// otherwise the connection is closed without sending a close frame.
+async fn fail_first_message<S>(
+    close_state: &mut CloseState,
+    framed: &mut FramedIo<S>,
+    error: Error,
+) -> Result<Message, Error>
+where
+    S: WebSocketStream,
+{
+    match framed.first_message().close_reason() {
+        Some(reason) => close(close_state, framed, Some(reason), Some(error)).await,
+        None => {
+            if framed.is_server() {
+                // 7.1.1: the TCP stream should be closed first by the server
+                framed.close().await;
+            }
+            *close_state = CloseState::Closed;
+            Err(error)
         }
     }
 }
@@ -453,22 +825,26 @@ where
 #[cfg(test)]
 mod tests {
     use crate::framed::Item;
+    use crate::protocol::MessageType;
     use crate::protocol::{ControlCode, DataCode, HeaderFlags, OpCode};
     use crate::ws::extension_encode;
     use crate::{
-        CloseCause, CloseCode, CloseReason, Error, Message, NegotiatedExtension, NoExt, Role,
-        WebSocket, WebSocketConfig, WebSocketStream,
+        CloseCause, CloseCode, CloseReason, Error, FragmentKind, Message, NegotiatedExtension,
+        NoExt, PayloadType, ProtocolError, Role, WebSocket, WebSocketClientBuilder,
+        WebSocketConfig, WebSocketServerBuilder, WebSocketStream,
     };
     use bytes::{Bytes, BytesMut};
     use ratchet_ext::Extension;
-    use tokio::io::{duplex, DuplexStream};
+    use std::time::Duration;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
+    use tokio::net::{TcpListener, TcpStream};
 
     impl<S, E> WebSocket<S, E>
     where
         S: WebSocketStream,
         E: Extension,
     {
-        pub async fn write_frame<A>(
+        pub(crate) async fn write_frame<A>(
             &mut self,
             buf: A,
             opcode: OpCode,
@@ -494,7 +870,11 @@ mod tests {
                 .await
         }
 
-        pub async fn read_frame(&mut self, read_buffer: &mut BytesMut) -> Result<Item, Error> {
+        #[allow(dead_code)]
+        pub(crate) async fn read_frame(
+            &mut self,
+            read_buffer: &mut BytesMut,
+        ) -> Result<Item, Error> {
             let WebSocket {
                 framed, extension, ..
             } = self;
@@ -528,105 +908,476 @@ mod tests {
         (client, server)
     }
 
-    #[tokio::test]
-    async fn ping_pong() {
-        let (mut client, mut server) = fixture();
-        let payload = "ping!";
-        client.write_ping(payload).await.expect("Send failed.");
-
-        let mut read_buf = BytesMut::new();
-        let message = server.read(&mut read_buf).await.expect("Read failure");
+    /// Writes `frames` to a server using `WebSocketConfig::strict` and returns the result of its
+    /// next read along with the peer's end of the stream.
+    async fn strict_read(frames: &[u8]) -> (Result<Message, Error>, BytesMut, DuplexStream) {
+        let (server, mut peer) = duplex(1024);
+        let mut server = WebSocket::from_upgraded(
+            WebSocketConfig::strict(),
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
 
-        assert_eq!(message, Message::Ping(Bytes::from("ping!")));
-        assert!(read_buf.is_empty());
+        peer.write_all(frames).await.unwrap();
+        let mut buf = BytesMut::new();
+        let result = server.read(&mut buf).await;
+        (result, buf, peer)
+    }
 
-        let message = client.read(&mut read_buf).await.expect("Read failure");
-        assert_eq!(message, Message::Pong(Bytes::from("ping!")));
-        assert!(read_buf.is_empty());
+    /// Returns a masked client frame with a zero masking key.
+    fn client_frame(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![first, 0x80 | payload.len() as u8, 0, 0, 0, 0];
+        frame.extend_from_slice(payload);
+        frame
     }
 
     #[tokio::test]
-    async fn reads_unsolicited_pong() {
-        let (mut client, mut server) = fixture();
-        let payload = "pong!";
-
-        let mut read_buf = BytesMut::new();
-        server.write_pong(payload).await.expect("Write failure");
+    async fn strict_config() {
+        // Framing: a text message is echoed intact.
+        let (result, buf, _) = strict_read(&client_frame(0x81, b"Hello")).await;
+        assert_eq!(result.unwrap(), Message::Text);
+        assert_eq!(buf.as_ref(), b"Hello");
+
+        // Pings and pongs: a ping is answered with a pong carrying its payload.
+        let (result, _, mut peer) = strict_read(&client_frame(0x89, b"ping")).await;
+        assert_eq!(result.unwrap(), Message::Ping(Bytes::from("ping")));
+        let mut pong = [0; 6];
+        peer.read_exact(&mut pong).await.unwrap();
+        assert_eq!(&pong, b"\x8a\x04ping");
+
+        // Reserved bits: RSV1 without a negotiated extension.
+        let (result, _, _) = strict_read(&client_frame(0xC1, b"Hello")).await;
+        assert!(result.unwrap_err().is_protocol());
+
+        // Opcodes: reserved data and control opcodes.
+        for first in [0x83, 0x8B] {
+            let (result, _, _) = strict_read(&client_frame(first, b"")).await;
+            assert!(result.unwrap_err().is_protocol());
+        }
 
-        let message = client.read(&mut read_buf).await.expect("Read failure");
-        assert_eq!(message, Message::Pong(Bytes::from(payload)));
-        assert!(read_buf.is_empty());
+        // Fragmentation: a ping interleaved with a fragmented text message.
+        let mut frames = client_frame(0x01, b"Hel");
+        frames.extend(client_frame(0x89, b""));
+        frames.extend(client_frame(0x80, b"lo"));
+        let (result, _, _) = strict_read(&frames).await;
+        assert_eq!(result.unwrap(), Message::Ping(Bytes::new()));
+
+        // UTF-8 handling: an invalid sequence split across fragments.
+        let mut frames = client_frame(0x01, b"\xce\xba\xe1");
+        frames.extend(client_frame(0x80, b"\xbd\xc0"));
+        let (result, _, _) = strict_read(&frames).await;
+        assert!(result.unwrap_err().is_encoding());
+
+        // Close handling: reserved close codes and invalid close reasons.
+        let (result, _, _) = strict_read(&client_frame(0x88, &999u16.to_be_bytes())).await;
+        assert!(result.unwrap_err().is_protocol());
+        let mut payload = 1000u16.to_be_bytes().to_vec();
+        payload.extend_from_slice(b"\xff");
+        let (result, _, _) = strict_read(&client_frame(0x88, &payload)).await;
+        assert!(result.unwrap_err().is_encoding());
+
+        // Masking: a server must fail the connection upon receiving an unmasked frame.
+        let (result, _, _) = strict_read(&[0x81, 0x02, b'h', b'i']).await;
+        assert!(result.unwrap_err().is_protocol());
     }
 
     #[tokio::test]
-    async fn empty_control_frame() {
-        let (mut client, mut server) = fixture();
-
-        let mut read_buf = BytesMut::new();
-        server.write_pong(&[]).await.expect("Write failure");
+    async fn validate_utf8_is_opt_in() {
+        let (server, mut peer) = duplex(1024);
+        let mut server = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
 
-        let message = client.read(&mut read_buf).await.expect("Read failure");
-        assert_eq!(message, Message::Pong(Bytes::new()));
-        assert!(read_buf.is_empty());
+        peer.write_all(&client_frame(0x81, b"\xff")).await.unwrap();
+        let mut buf = BytesMut::new();
+        assert_eq!(server.read(&mut buf).await.unwrap(), Message::Text);
+        assert_eq!(buf.as_ref(), b"\xff");
     }
 
     #[tokio::test]
-    async fn interleaved_control_frames() {
-        let (mut client, mut server) = fixture();
-        let control_data = "data";
+    async fn from_http_upgrade() {
+        let (server, mut peer) = duplex(1024);
+        let mut server = WebSocket::from_http_upgrade(
+            WebSocketConfig::default(),
+            server,
+            Some("warp".to_string()),
+            NegotiatedExtension::from(NoExt),
+            BytesMut::from(client_frame(0x81, b"leftover").as_slice()),
+            Role::Server,
+        );
 
-        client
-            .write_frame("123", OpCode::DataCode(DataCode::Text), false)
-            .await
-            .expect("Write failure");
-        client
-            .write_frame("456", OpCode::DataCode(DataCode::Continuation), false)
-            .await
-            .expect("Write failure");
+        assert_eq!(server.role(), Role::Server);
+        assert_eq!(server.subprotocol(), Some("warp"));
 
-        client
-            .write_frame(control_data, OpCode::ControlCode(ControlCode::Ping), true)
-            .await
-            .expect("Write failure");
+        peer.write_all(&client_frame(0x82, b"next")).await.unwrap();
 
-        client
-            .write_frame(control_data, OpCode::ControlCode(ControlCode::Pong), true)
-            .await
-            .expect("Write failure");
+        let mut buf = BytesMut::new();
+        assert_eq!(server.read(&mut buf).await.unwrap(), Message::Text);
+        assert_eq!(buf.as_ref(), b"leftover");
 
-        client
-            .write_frame("789", OpCode::DataCode(DataCode::Continuation), true)
-            .await
-            .expect("Write failure");
+        buf.clear();
+        assert_eq!(server.read(&mut buf).await.unwrap(), Message::Binary);
+        assert_eq!(buf.as_ref(), b"next");
+    }
 
-        let mut buf = BytesMut::new();
-        let message = server.read(&mut buf).await.expect("Read failure");
+    #[tokio::test]
+    async fn cancellation_safe_read() {
+        use futures::FutureExt;
 
-        assert_eq!(message, Message::Ping(Bytes::from(control_data)));
-        assert!(!buf.is_empty());
+        let (server, mut peer) = duplex(1024);
+        let mut server = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
 
-        let message = server.read(&mut buf).await.expect("Read failure");
+        // A fragmented binary message with a 16-bit payload length in its first frame and a
+        // non-zero masking key.
+        let payload = (0..300).map(|i| i as u8).collect::<Vec<_>>();
+        let mask = [1, 2, 3, 4];
+        let mut frames = vec![0x02, 0x80 | 126, 0x01, 0x2C];
+        frames.extend_from_slice(&mask);
+        frames.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frames.extend_from_slice(&[0x80, 0x83, 0, 0, 0, 0, 1, 2, 3]);
 
-        assert_eq!(message, Message::Pong(Bytes::from(control_data)));
-        assert!(!buf.is_empty());
+        let mut buf = BytesMut::new();
+        for byte in frames {
+            // Each read consumes the available bytes and is then dropped before it completes.
+            assert!(server.read(&mut buf).now_or_never().is_none());
+            peer.write_all(&[byte]).await.unwrap();
+        }
 
         let message = server.read(&mut buf).await.expect("Read failure");
+        assert_eq!(message, Message::Binary);
+        assert_eq!(buf[..300], payload[..]);
+        assert_eq!(buf[300..], [1, 2, 3]);
+    }
 
-        assert_eq!(message, Message::Text);
-        assert!(!buf.is_empty());
-
-        assert_eq!(
-            String::from_utf8(buf.to_vec()).expect("Malformatted data received"),
-            "123456789"
+    #[tokio::test(start_paused = true)]
+    async fn write_timeout() {
+        let (client, mut peer) = duplex(64);
+        let config = WebSocketConfig {
+            write_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let mut client = WebSocket::from_upgraded(
+            config,
+            client,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Client,
         );
+
+        // A frame which fits in the stream's buffer completes.
+        client.write_binary([1; 16]).await.expect("Write failure");
+
+        // The peer never reads and so this frame cannot be written.
+        let error = client
+            .write_binary([1; 1024])
+            .await
+            .expect_err("Expected the write to time out");
+        assert!(error.is_timeout());
+
+        // The frame may have been partially written and so all further writes are refused.
+        assert!(client.is_closed());
+        let error = client
+            .write_binary([1; 1])
+            .await
+            .expect_err("Expected the write to be refused");
+        assert_eq!(error.downcast_ref::<CloseCause>(), Some(&CloseCause::Error));
+        client
+            .close(CloseReason::new(CloseCode::Normal, None))
+            .await
+            .expect("Closing a closed WebSocket is a no-op");
+
+        let mut frame = [0; 22];
+        peer.read_exact(&mut frame).await.unwrap();
+        assert_eq!(frame[..2], [0x82, 0x90]);
     }
 
     #[tokio::test]
-    async fn large_control_frames() {
-        {
+    async fn max_fragments() {
+        let (server, mut peer) = duplex(1024);
+        let config = WebSocketConfig {
+            max_fragments: Some(10),
+            ..Default::default()
+        };
+        let mut server = WebSocket::from_upgraded(
+            config,
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
+
+        let mut frames = client_frame(0x02, b"a");
+        for i in 1..100 {
+            let first = if i == 99 { 0x80 } else { 0x00 };
+            frames.extend(client_frame(first, b"a"));
+        }
+        peer.write_all(&frames).await.unwrap();
+
+        let err = server
+            .read(&mut BytesMut::new())
+            .await
+            .expect_err("Expected too many fragments");
+        assert!(err.is_protocol());
+        assert_eq!(
+            err.downcast_ref::<ProtocolError>(),
+            Some(&ProtocolError::TooManyFragments)
+        );
+    }
+
+    #[tokio::test]
+    async fn max_fragments_permits_limit() {
+        let (server, mut peer) = duplex(1024);
+        let config = WebSocketConfig {
+            max_fragments: Some(10),
+            ..Default::default()
+        };
+        let mut server = WebSocket::from_upgraded(
+            config,
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
+
+        // Two messages of ten fragments each, as the count is reset for each message.
+        for _ in 0..2 {
+            let mut frames = client_frame(0x02, b"a");
+            for i in 1..10 {
+                let first = if i == 9 { 0x80 } else { 0x00 };
+                frames.extend(client_frame(first, b"a"));
+            }
+            peer.write_all(&frames).await.unwrap();
+        }
+
+        let mut buf = BytesMut::new();
+        for _ in 0..2 {
+            assert_eq!(server.read(&mut buf).await.unwrap(), Message::Binary);
+            assert_eq!(buf.as_ref(), [b'a'; 10]);
+            buf.clear();
+        }
+    }
+
+    #[tokio::test]
+    async fn max_outgoing_frame_size() {
+        let (server, client) = duplex(512);
+        let config = WebSocketConfig {
+            max_outgoing_frame_size: Some(1024),
+            ..Default::default()
+        };
+
+        let mut server = WebSocket::from_upgraded(
+            config,
+            server,
+            NegotiatedExtension::from(None::<NoExt>),
+            BytesMut::new(),
+            Role::Server,
+        );
+        let mut client = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            client,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Client,
+        );
+
+        let payload = Bytes::from((0..10 * 1024).map(|i| i as u8).collect::<Vec<_>>());
+        let write = async {
+            server.write_binary(&payload).await.expect("Write failure");
+            server
+                .write_frame_bytes(payload.clone(), FragmentKind::Binary, true)
+                .await
+                .expect("Write failure");
+        };
+        let read = async {
+            let mut buf = BytesMut::new();
+            for _ in 0..2 {
+                let message = client.read(&mut buf).await.expect("Read failure");
+                assert_eq!(message, Message::Binary);
+                assert_eq!(buf.as_ref(), payload.as_ref());
+                buf.clear();
+            }
+        };
+
+        tokio::join!(write, read);
+        // Each message was written as ten 1 KiB frames, each with a 4 byte header.
+        assert_eq!(server.byte_counts().1, 2 * 10 * (1024 + 4));
+    }
+
+    #[tokio::test]
+    async fn ping_pong() {
+        let (mut client, mut server) = fixture();
+        let payload = "ping!";
+        client.write_ping(payload).await.expect("Send failed.");
+
+        let mut read_buf = BytesMut::new();
+        let message = server.read(&mut read_buf).await.expect("Read failure");
+
+        assert_eq!(message, Message::Ping(Bytes::from("ping!")));
+        assert!(read_buf.is_empty());
+
+        let message = client.read(&mut read_buf).await.expect("Read failure");
+        assert_eq!(message, Message::Pong(Bytes::from("ping!")));
+        assert!(read_buf.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn min_auto_pong_interval() {
+        let (client, server) = duplex(512);
+        let mut client = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            client,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Client,
+        );
+        let mut server = WebSocket::from_upgraded(
+            WebSocketConfig {
+                min_auto_pong_interval: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
+
+        async fn flood(
+            client: &mut WebSocket<DuplexStream, NoExt>,
+            server: &mut WebSocket<DuplexStream, NoExt>,
+        ) -> usize {
+            let write = async {
+                for _ in 0..1000 {
+                    client.write_ping("ping").await.expect("Write failure");
+                }
+            };
+            let read = async {
+                let mut buf = BytesMut::new();
+                for _ in 0..1000 {
+                    let message = server.read(&mut buf).await.expect("Read failure");
+                    assert_eq!(message, Message::Ping(Bytes::from("ping")));
+                }
+                server.write_text("done").await.expect("Write failure");
+            };
+            tokio::join!(write, read);
+
+            let mut buf = BytesMut::new();
+            let mut pongs = 0;
+            loop {
+                match client.read(&mut buf).await.expect("Read failure") {
+                    Message::Pong(payload) => {
+                        assert_eq!(payload, Bytes::from("ping"));
+                        pongs += 1;
+                    }
+                    Message::Text => break pongs,
+                    message => panic!("Unexpected message: {:?}", message),
+                }
+            }
+        }
+
+        assert_eq!(flood(&mut client, &mut server).await, 1);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(flood(&mut client, &mut server).await, 1);
+    }
+
+    #[tokio::test]
+    async fn reads_unsolicited_pong() {
+        let (mut client, mut server) = fixture();
+        let payload = "pong!";
+
+        let mut read_buf = BytesMut::new();
+        server.write_pong(payload).await.expect("Write failure");
+
+        let message = client.read(&mut read_buf).await.expect("Read failure");
+        assert_eq!(message, Message::Pong(Bytes::from(payload)));
+        assert!(read_buf.is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_control_frame() {
+        let (mut client, mut server) = fixture();
+
+        let mut read_buf = BytesMut::new();
+        server.write_pong(&[]).await.expect("Write failure");
+
+        let message = client.read(&mut read_buf).await.expect("Read failure");
+        assert_eq!(message, Message::Pong(Bytes::new()));
+        assert!(read_buf.is_empty());
+    }
+
+    #[tokio::test]
+    async fn interleaved_control_frames() {
+        let (mut client, mut server) = fixture();
+        let control_data = "data";
+
+        client
+            .write_frame("123", OpCode::DataCode(DataCode::Text), false)
+            .await
+            .expect("Write failure");
+        client
+            .write_frame("456", OpCode::DataCode(DataCode::Continuation), false)
+            .await
+            .expect("Write failure");
+
+        client
+            .write_frame(control_data, OpCode::ControlCode(ControlCode::Ping), true)
+            .await
+            .expect("Write failure");
+
+        client
+            .write_frame(control_data, OpCode::ControlCode(ControlCode::Pong), true)
+            .await
+            .expect("Write failure");
+
+        client
+            .write_frame("789", OpCode::DataCode(DataCode::Continuation), true)
+            .await
+            .expect("Write failure");
+
+        let mut buf = BytesMut::new();
+        let message = server.read(&mut buf).await.expect("Read failure");
+
+        assert_eq!(message, Message::Ping(Bytes::from(control_data)));
+        assert!(!buf.is_empty());
+
+        let message = server.read(&mut buf).await.expect("Read failure");
+
+        assert_eq!(message, Message::Pong(Bytes::from(control_data)));
+        assert!(!buf.is_empty());
+
+        let message = server.read(&mut buf).await.expect("Read failure");
+
+        assert_eq!(message, Message::Text);
+        assert!(!buf.is_empty());
+
+        assert_eq!(
+            String::from_utf8(buf.to_vec()).expect("Malformatted data received"),
+            "123456789"
+        );
+    }
+
+    #[tokio::test]
+    async fn large_control_frames() {
+        {
             let (mut client, _server) = fixture();
             let error = client.write_ping(&[13; 256]).await.unwrap_err();
             assert!(error.is_protocol());
+            assert_eq!(
+                error.protocol_error(),
+                Some(&ProtocolError::ControlFrameOverflow)
+            );
         }
         {
             let (mut client, mut server) = fixture();
@@ -637,6 +1388,10 @@ mod tests {
 
             let error = client.read(&mut BytesMut::new()).await.unwrap_err();
             assert!(error.is_protocol());
+            assert_eq!(
+                error.protocol_error(),
+                Some(&ProtocolError::ControlFrameOverflow)
+            );
         }
     }
 
@@ -806,4 +1561,614 @@ mod tests {
             &CloseCause::Error
         );
     }
+
+    #[tokio::test]
+    async fn masking_key_gen() {
+        let (client, mut peer) = duplex(512);
+        let mut client = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            client,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Client,
+        );
+        client.set_masking_key_gen(|| 0x3d21fa37);
+        client.write_text("Hello").await.expect("Write failure");
+
+        // https://datatracker.ietf.org/doc/html/rfc6455#section-5.7
+        let mut buf = [0; 11];
+        peer.read_exact(&mut buf).await.expect("Read failure");
+        assert_eq!(
+            buf,
+            [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58]
+        );
+    }
+
+    #[tokio::test]
+    async fn masking_key_gen_server() {
+        let (server, mut peer) = duplex(512);
+        let mut server = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
+        server.set_masking_key_gen(|| 0x3d21fa37);
+        server.write_text("Hello").await.expect("Write failure");
+
+        let mut buf = [0; 7];
+        peer.read_exact(&mut buf).await.expect("Read failure");
+        assert_eq!(buf, [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]);
+    }
+
+    fn expected_first_fixture(
+        close_on_unexpected_opcode: bool,
+    ) -> (
+        WebSocket<DuplexStream, NoExt>,
+        WebSocket<DuplexStream, NoExt>,
+    ) {
+        let (server, client) = duplex(512);
+        let server = WebSocket::from_upgraded(
+            WebSocketConfig {
+                expected_first_opcode: Some(MessageType::Text),
+                close_on_unexpected_opcode,
+                ..Default::default()
+            },
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
+        let client = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            client,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Client,
+        );
+
+        (client, server)
+    }
+
+    #[tokio::test]
+    async fn unexpected_first_opcode() {
+        let (mut client, mut server) = expected_first_fixture(false);
+
+        client.write_ping("ping").await.expect("Write failure");
+        client.write_binary("binary").await.expect("Write failure");
+
+        let mut buf = BytesMut::new();
+        let message = server.read(&mut buf).await.expect("Read failure");
+        assert_eq!(message, Message::Ping(Bytes::from("ping")));
+
+        let err = server.read(&mut buf).await.expect_err("Expected an error");
+        assert!(err.is_protocol());
+        assert_eq!(
+            err.downcast_ref::<ProtocolError>(),
+            Some(&ProtocolError::UnexpectedOpCode)
+        );
+    }
+
+    #[tokio::test]
+    async fn unexpected_first_opcode_no_close() {
+        let (server, mut peer) = duplex(512);
+        let mut server = WebSocket::from_upgraded(
+            WebSocketConfig {
+                expected_first_opcode: Some(MessageType::Text),
+                close_on_unexpected_opcode: false,
+                ..Default::default()
+            },
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
+
+        // A masked binary frame with a zeroed masking key.
+        peer.write_all(&[0x82, 0x82, 0x00, 0x00, 0x00, 0x00, b'h', b'i'])
+            .await
+            .expect("Write failure");
+
+        let mut buf = BytesMut::new();
+        let err = server.read(&mut buf).await.expect_err("Expected an error");
+        assert_eq!(
+            err.downcast_ref::<ProtocolError>(),
+            Some(&ProtocolError::UnexpectedOpCode)
+        );
+        assert!(server.is_closed());
+
+        // The connection has failed and so later messages are not read.
+        peer.write_all(&[0x81, 0x82, 0x00, 0x00, 0x00, 0x00, b'h', b'i'])
+            .await
+            .expect("Write failure");
+        let err = server.read(&mut buf).await.expect_err("Expected an error");
+        assert_eq!(err.downcast_ref::<CloseCause>(), Some(&CloseCause::Error));
+
+        // The server shut down the stream without writing a close frame.
+        let mut written = Vec::new();
+        peer.read_to_end(&mut written).await.expect("Read failure");
+        assert!(written.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unexpected_first_opcode_policy_close() {
+        let (mut client, mut server) = expected_first_fixture(true);
+
+        client.write_binary("binary").await.expect("Write failure");
+
+        let mut buf = BytesMut::new();
+        let err = server.read(&mut buf).await.expect_err("Expected an error");
+        assert!(err.is_protocol());
+
+        let message = client.read(&mut buf).await.expect("Read failure");
+        assert_eq!(
+            message,
+            Message::Close(Some(CloseReason::new(CloseCode::Policy, None)))
+        );
+    }
+
+    #[tokio::test]
+    async fn expected_first_opcode() {
+        let (mut client, mut server) = expected_first_fixture(true);
+
+        client.write_text("text").await.expect("Write failure");
+        client.write_binary("binary").await.expect("Write failure");
+
+        let mut buf = BytesMut::new();
+        assert_eq!(server.read(&mut buf).await.unwrap(), Message::Text);
+        buf.clear();
+        assert_eq!(server.read(&mut buf).await.unwrap(), Message::Binary);
+    }
+
+    #[tokio::test]
+    async fn close_mid_fragment() {
+        let (client, mut peer) = duplex(512);
+        let mut client = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            client,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Client,
+        );
+
+        // A text frame without the FIN bit set that is followed by a close frame.
+        peer.write_all(&[0x01, 0x07, b'p', b'a', b'r', b't', b'i', b'a', b'l'])
+            .await
+            .expect("Write failure");
+        peer.write_all(&[0x88, 0x02, 0x03, 0xe8])
+            .await
+            .expect("Write failure");
+
+        let mut buf = BytesMut::from("previous");
+        let message = client.read(&mut buf).await.expect("Read failure");
+        assert_eq!(
+            message,
+            Message::Close(Some(CloseReason::new(CloseCode::Normal, None)))
+        );
+        assert_eq!(buf.as_ref(), b"previous");
+    }
+
+    #[tokio::test]
+    async fn write_frame_bytes() {
+        let (server, client) = duplex(512);
+        let mut server = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            server,
+            NegotiatedExtension::<NoExt>::from(None),
+            BytesMut::new(),
+            Role::Server,
+        );
+        let mut client = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            client,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Client,
+        );
+
+        server
+            .write_frame_bytes(Bytes::from_static(b"binary"), FragmentKind::Binary, true)
+            .await
+            .expect("Write failure");
+        assert_eq!(server.byte_counts(), (0, 8));
+
+        client
+            .write_frame_bytes(Bytes::from_static(b"text"), FragmentKind::Text, true)
+            .await
+            .expect("Write failure");
+
+        let mut buf = BytesMut::new();
+        assert_eq!(client.read(&mut buf).await.unwrap(), Message::Binary);
+        assert_eq!(buf.as_ref(), b"binary");
+        buf.clear();
+
+        assert_eq!(server.read(&mut buf).await.unwrap(), Message::Text);
+        assert_eq!(buf.as_ref(), b"text");
+    }
+
+    #[tokio::test]
+    async fn write_frame_bytes_fragments() {
+        for role in [Role::Server, Role::Client] {
+            let (stream, mut peer) = duplex(512);
+            let mut socket = WebSocket::from_upgraded(
+                WebSocketConfig::default(),
+                stream,
+                NegotiatedExtension::from(NoExt),
+                BytesMut::new(),
+                role,
+            );
+            socket.set_masking_key_gen(|| 0);
+
+            socket
+                .write_frame_bytes(Bytes::from_static(b"Hel"), FragmentKind::Text, false)
+                .await
+                .expect("Write failure");
+            socket
+                .write_frame_bytes(Bytes::from_static(b"lo"), FragmentKind::Continuation, true)
+                .await
+                .expect("Write failure");
+            drop(socket);
+
+            let mut frames = Vec::new();
+            peer.read_to_end(&mut frames).await.expect("Read failure");
+
+            let expected: &[u8] = match role {
+                Role::Server => &[0x01, 0x03, b'H', b'e', b'l', 0x80, 0x02, b'l', b'o'],
+                Role::Client => &[
+                    0x01, 0x83, 0, 0, 0, 0, b'H', b'e', b'l', 0x80, 0x82, 0, 0, 0, 0, b'l', b'o',
+                ],
+            };
+            assert_eq!(frames, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn set_max_message_size() {
+        let (client, mut peer) = duplex(512);
+        let mut client = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            client,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Client,
+        );
+
+        // The first fragment of a text message, interrupted by a ping.
+        peer.write_all(&[0x01, 0x05, b'h', b'e', b'l', b'l', b'o'])
+            .await
+            .expect("Write failure");
+        peer.write_all(&[0x89, 0x00]).await.expect("Write failure");
+
+        let mut buf = BytesMut::new();
+        assert_eq!(
+            client.read(&mut buf).await.unwrap(),
+            Message::Ping(Bytes::new())
+        );
+
+        client.set_max_message_size(8);
+
+        // The in-flight message is still bounded by the limit that it started with.
+        peer.write_all(&[0x80, 0x06, b' ', b'w', b'o', b'r', b'l', b'd'])
+            .await
+            .expect("Write failure");
+        assert_eq!(client.read(&mut buf).await.unwrap(), Message::Text);
+        assert_eq!(buf.as_ref(), b"hello world");
+        buf.clear();
+
+        peer.write_all(&[0x82, 0x09, 0, 1, 2, 3, 4, 5, 6, 7, 8])
+            .await
+            .expect("Write failure");
+        let err = client.read(&mut buf).await.unwrap_err();
+        assert!(err.is_protocol());
+        assert_eq!(
+            err.downcast_ref::<ProtocolError>(),
+            Some(&ProtocolError::FrameOverflow)
+        );
+    }
+
+    #[tokio::test]
+    async fn unlimited_message_size() {
+        async fn transfer(max_message_size: usize, len: usize) -> Result<BytesMut, Error> {
+            let (client, server) = duplex(64 * 1024);
+            let config = WebSocketConfig {
+                max_message_size,
+                ..Default::default()
+            };
+            let mut client = WebSocket::from_upgraded(
+                config,
+                client,
+                NegotiatedExtension::from(NoExt),
+                BytesMut::new(),
+                Role::Client,
+            );
+            let mut server = WebSocket::from_upgraded(
+                config,
+                server,
+                NegotiatedExtension::from(NoExt),
+                BytesMut::new(),
+                Role::Server,
+            );
+
+            // The write is not completed if the client fails the read part way through.
+            let write = tokio::spawn(async move {
+                server
+                    .write_binary(vec![7; len])
+                    .await
+                    .expect("Write failure");
+            });
+            let mut buf = BytesMut::new();
+            let result = client.read(&mut buf).await;
+            write.abort();
+
+            result.map(|message| {
+                assert_eq!(message, Message::Binary);
+                buf
+            })
+        }
+
+        let len = 4 << 20;
+        let buf = transfer(0, len).await.expect("Read failure");
+        assert_eq!(buf.len(), len);
+        assert!(buf.iter().all(|b| *b == 7));
+
+        let err = transfer(1 << 20, len).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ProtocolError>(),
+            Some(&ProtocolError::FrameOverflow)
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_prefix_buffer() {
+        let (client, mut peer) = duplex(512);
+        // A frame that was read by the handshake along with the start of the next one.
+        let prefix = BytesMut::from(&[0x81, 0x05, b'f', b'i', b'r', b's', b't', 0x82, 0x06][..]);
+        let mut client = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            client,
+            NegotiatedExtension::from(NoExt),
+            prefix,
+            Role::Client,
+        );
+
+        peer.write_all(b"second").await.expect("Write failure");
+        peer.write_all(&[0x81, 0x05, b't', b'h', b'i', b'r', b'd'])
+            .await
+            .expect("Write failure");
+
+        let mut buf = BytesMut::new();
+        assert_eq!(client.read(&mut buf).await.unwrap(), Message::Text);
+        assert_eq!(buf.as_ref(), b"first");
+        buf.clear();
+
+        assert_eq!(client.read(&mut buf).await.unwrap(), Message::Binary);
+        assert_eq!(buf.as_ref(), b"second");
+        buf.clear();
+
+        assert_eq!(client.read(&mut buf).await.unwrap(), Message::Text);
+        assert_eq!(buf.as_ref(), b"third");
+    }
+
+    #[tokio::test]
+    async fn rejects_rsv_bits_without_extension() {
+        for rsv in [0x40, 0x20, 0x10] {
+            let (client, mut peer) = duplex(512);
+            let mut client = WebSocket::from_upgraded(
+                WebSocketConfig::default(),
+                client,
+                NegotiatedExtension::from(NoExt),
+                BytesMut::new(),
+                Role::Client,
+            );
+
+            peer.write_all(&[0x81 | rsv, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f])
+                .await
+                .expect("Write failure");
+
+            let mut buf = BytesMut::new();
+            let err = client
+                .read(&mut buf)
+                .await
+                .expect_err("Expected a read failure");
+            assert!(err.is_protocol());
+            assert_eq!(
+                err.downcast_ref::<ProtocolError>(),
+                Some(&ProtocolError::UnknownExtension)
+            );
+        }
+    }
+
+    async fn read_unmasked_frame(config: WebSocketConfig) -> Result<Message, Error> {
+        let (server, mut peer) = duplex(512);
+        let mut server = WebSocket::from_upgraded(
+            config,
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
+
+        peer.write_all(&[0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f])
+            .await
+            .expect("Write failure");
+
+        let mut buf = BytesMut::new();
+        let result = server.read(&mut buf).await;
+        if result.is_ok() {
+            assert_eq!(buf.as_ref(), b"Hello");
+        }
+        result
+    }
+
+    #[tokio::test]
+    async fn rejects_unmasked_frames() {
+        let err = read_unmasked_frame(WebSocketConfig::default())
+            .await
+            .expect_err("Expected a read failure");
+        assert!(err.is_protocol());
+        assert_eq!(
+            err.downcast_ref::<ProtocolError>(),
+            Some(&ProtocolError::UnmaskedFrame)
+        );
+    }
+
+    #[tokio::test]
+    async fn accepts_unmasked_frames() {
+        let config = WebSocketConfig {
+            accept_unmasked_frames: true,
+            ..Default::default()
+        };
+        let message = read_unmasked_frame(config).await.expect("Read failure");
+        assert_eq!(message, Message::Text);
+    }
+
+    #[test]
+    fn constraints() {
+        let (client, server) = fixture();
+
+        let client_constraints = client.constraints();
+        assert!(client_constraints.must_mask);
+        assert_eq!(client_constraints.max_control_size, 125);
+
+        let server_constraints = server.constraints();
+        assert!(!server_constraints.must_mask);
+        assert_eq!(server_constraints.max_control_size, 125);
+
+        for constraints in [client_constraints, server_constraints] {
+            assert!(constraints.is_opcode_allowed(0x1));
+            assert!(constraints.is_opcode_allowed(0x9));
+            assert!(!constraints.is_opcode_allowed(0x3));
+            assert!(constraints.is_rsv_allowed(false, false, false));
+            assert!(!constraints.is_rsv_allowed(true, false, false));
+        }
+    }
+
+    #[tokio::test]
+    async fn peer_addr() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener_addr = listener.local_addr().unwrap();
+
+        let (client, server) = tokio::join!(TcpStream::connect(listener_addr), listener.accept());
+        let client = client.unwrap();
+        let (server, server_peer_addr) = server.unwrap();
+
+        let client = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            client,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Client,
+        );
+        let server = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            server,
+            NegotiatedExtension::from(NoExt),
+            BytesMut::new(),
+            Role::Server,
+        );
+
+        assert_eq!(client.peer_addr().unwrap(), listener_addr);
+        assert_eq!(client.local_addr().unwrap(), server_peer_addr);
+        assert_eq!(server.peer_addr().unwrap(), server_peer_addr);
+        assert_eq!(server.local_addr().unwrap(), listener_addr);
+    }
+
+    async fn builder_nodelay(nodelay: bool) -> (bool, bool) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener_addr = listener.local_addr().unwrap();
+
+        let client = async {
+            let stream = TcpStream::connect(listener_addr).await.unwrap();
+            WebSocketClientBuilder::default()
+                .nodelay(nodelay)
+                .subscribe(stream, format!("ws://{}", listener_addr))
+                .await
+                .unwrap()
+        };
+        let server = async {
+            let (stream, _) = listener.accept().await.unwrap();
+            WebSocketServerBuilder::default()
+                .nodelay(nodelay)
+                .accept(stream)
+                .await
+                .unwrap()
+        };
+
+        let (client, server) = tokio::join!(client, server);
+        (
+            client.websocket.framed.io().nodelay().unwrap(),
+            server.websocket.framed.io().nodelay().unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn nodelay() {
+        assert_eq!(builder_nodelay(true).await, (true, true));
+        assert_eq!(builder_nodelay(false).await, (false, false));
+    }
+
+    #[tokio::test]
+    async fn byte_counts() {
+        let (mut client, mut server) = fixture();
+        assert_eq!(client.byte_counts(), (0, 0));
+
+        client.write_text("hello").await.expect("Write failure");
+        // A two byte header, a four byte mask and the payload.
+        assert_eq!(client.byte_counts(), (0, 11));
+
+        let mut buf = BytesMut::new();
+        server.read(&mut buf).await.expect("Read failure");
+        assert_eq!(server.byte_counts(), (11, 0));
+
+        server.write_binary("hi").await.expect("Write failure");
+        assert_eq!(server.byte_counts(), (11, 4));
+
+        client.read(&mut buf).await.expect("Read failure");
+        assert_eq!(client.byte_counts(), (4, 11));
+    }
+
+    #[test]
+    fn extensions_summary() {
+        let (client, server) = fixture();
+        assert_eq!(client.extensions_summary(), "none");
+        assert_eq!(server.extensions_summary(), "none");
+    }
+
+    #[tokio::test]
+    async fn going_away() {
+        let (mut client, mut server) = fixture();
+
+        server.going_away().await.expect("Write failure");
+        assert!(!server.is_active());
+
+        let mut buf = BytesMut::new();
+        let message = client.read(&mut buf).await.expect("Read failure");
+        assert_eq!(
+            message,
+            Message::Close(Some(CloseReason::new(CloseCode::GoingAway, None)))
+        );
+
+        let err = server.read(&mut buf).await.expect_err("Expected an error");
+        assert!(err.is_close());
+        assert!(server.is_closed());
+    }
+
+    #[tokio::test]
+    async fn send_text_round_trip() {
+        let (mut client, mut server) = fixture();
+        let mut buf = BytesMut::new();
+
+        client.write_text("hello").await.expect("Write failure");
+        assert_eq!(server.read(&mut buf).await.unwrap(), Message::Text);
+        assert_eq!(buf.as_ref(), b"hello");
+        buf.clear();
+
+        server
+            .write(Bytes::from("hello"), PayloadType::Binary)
+            .await
+            .expect("Write failure");
+        assert_eq!(client.read(&mut buf).await.unwrap(), Message::Binary);
+        assert_eq!(buf.as_ref(), b"hello");
+    }
 }
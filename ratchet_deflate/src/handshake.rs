@@ -23,12 +23,12 @@ use std::fmt::Write;
 use std::str::Utf8Error;
 
 /// The WebSocket Extension Identifier as per the IANA registry.
-const EXT_IDENT: &str = "permessage-deflate";
+pub const EXT_IDENT: &str = "permessage-deflate";
 
-const SERVER_MAX_BITS: &str = "server_max_window_bits";
-const CLIENT_MAX_BITS: &str = "client_max_window_bits";
-const SERVER_NO_TAKEOVER: &str = "server_no_context_takeover";
-const CLIENT_NO_TAKEOVER: &str = "client_no_context_takeover";
+pub const SERVER_MAX_BITS: &str = "server_max_window_bits";
+pub const CLIENT_MAX_BITS: &str = "client_max_window_bits";
+pub const SERVER_NO_TAKEOVER: &str = "server_no_context_takeover";
+pub const CLIENT_NO_TAKEOVER: &str = "client_no_context_takeover";
 const ERR_TAKEOVER: &str = "The client requires context takeover";
 const UNKNOWN_PARAM: &str = "Unknown permessage-deflate parameter";
 const DUPLICATE_PARAM: &str = "Duplicate permessage-deflate parameter";
@@ -41,6 +41,7 @@ pub struct InitialisedDeflateConfig {
     pub compress_reset: bool,
     pub decompress_reset: bool,
     pub compression_level: Compression,
+    pub retain_state: bool,
 }
 
 impl InitialisedDeflateConfig {
@@ -51,6 +52,7 @@ impl InitialisedDeflateConfig {
             compress_reset: config.accept_no_context_takeover,
             decompress_reset: false,
             compression_level: config.compression_level,
+            retain_state: config.retain_state,
         }
     }
 }
@@ -414,6 +416,7 @@ pub fn on_response(
             compress_reset,
             decompress_reset,
             compression_level: config.compression_level,
+            retain_state: config.retain_state,
         })
     } else {
         Err(NegotiationErr::Failed)
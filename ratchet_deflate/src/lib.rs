@@ -22,20 +22,22 @@
 use std::cmp::Ordering;
 use std::convert::TryFrom;
 
-use bytes::BytesMut;
+use bytes::{Buf, Bytes, BytesMut};
 pub use flate2::Compression;
 use flate2::{Compress, Decompress, FlushCompress, FlushDecompress, Status};
 use thiserror::Error;
 
 pub use error::DeflateExtensionError;
 use ratchet_ext::{
-    Extension, ExtensionDecoder, ExtensionEncoder, ExtensionProvider, FrameHeader, Header,
-    HeaderMap, HeaderValue, OpCode, ReunitableExtension, RsvBits, SplittableExtension,
+    Extension, ExtensionDecoder, ExtensionEncoder, ExtensionProvider, ExtensionState,
+    ExtensionStats, FrameHeader, Header, HeaderMap, HeaderValue, OpCode, ReunitableExtension,
+    RsvBits, SplittableExtension,
 };
 
 use crate::codec::{BufCompress, BufDecompress};
 use crate::handshake::{
-    apply_headers, negotiate_client, negotiate_server, InitialisedDeflateConfig,
+    apply_headers, negotiate_client, negotiate_server, InitialisedDeflateConfig, CLIENT_MAX_BITS,
+    CLIENT_NO_TAKEOVER, EXT_IDENT, SERVER_MAX_BITS, SERVER_NO_TAKEOVER,
 };
 
 #[cfg(test)]
@@ -94,6 +96,11 @@ impl ExtensionProvider for DeflateExtProvider {
 }
 
 /// Client or server maximum window bits. Wrapping a `u8` with a value in the range of 8..=15.
+///
+/// # Note
+/// zlib does not support an LZ77 window of 8 bits and so a window of 9 bits is used in its place
+/// when a window of 8 bits has been negotiated. A peer that strictly enforces a window of 8 bits
+/// may fail to decompress messages that reference data 257 to 512 bytes back.
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
 pub struct WindowBits(u8);
 
@@ -201,8 +208,17 @@ pub struct DeflateConfig {
     /// Whether to accept `no_context_takeover`.
     pub accept_no_context_takeover: bool,
     /// The active compression level. The integer here is typically on a scale of 0-9 where 0 means
-    /// "no compression" and 9 means "take as long as you'd like".
+    /// "no compression" and 9 means "take as long as you'd like". This is a local setting and is
+    /// not negotiated with the peer.
+    ///
+    /// The compressor's memory level is not configurable and is always zlib's default of 8.
     pub compression_level: Compression,
+    /// Whether to retain the most recent data that was compressed and decompressed when context
+    /// takeover is in use, so that the state of the extension may be saved using
+    /// `ExtensionState::save`; otherwise the state of such a session cannot be saved. This copies
+    /// every message into a buffer of up to the size of the LZ77 window and so is disabled by
+    /// default. This is a local setting and is not negotiated with the peer.
+    pub retain_state: bool,
 }
 
 impl Default for DeflateConfig {
@@ -214,6 +230,7 @@ impl Default for DeflateConfig {
             request_client_no_context_takeover: true,
             accept_no_context_takeover: true,
             compression_level: Compression::fast(),
+            retain_state: false,
         }
     }
 }
@@ -227,29 +244,62 @@ pub struct Deflate {
 }
 
 impl Deflate {
+    /// Returns the negotiated size of the server's LZ77 sliding window.
+    pub fn server_max_window_bits(&self) -> WindowBits {
+        self.parameters().0 .0
+    }
+
+    /// Returns the negotiated size of the client's LZ77 sliding window.
+    pub fn client_max_window_bits(&self) -> WindowBits {
+        self.parameters().1 .0
+    }
+
+    // Returns the window size and whether the window is reset between messages for the server and
+    // the client, respectively.
+    fn parameters(&self) -> ((WindowBits, bool), (WindowBits, bool)) {
+        let Deflate { encoder, decoder } = self;
+        if encoder.is_server {
+            (
+                (encoder.window_bits, encoder.compress_reset),
+                (decoder.window_bits, decoder.decompress_reset),
+            )
+        } else {
+            (
+                (decoder.window_bits, decoder.decompress_reset),
+                (encoder.window_bits, encoder.compress_reset),
+            )
+        }
+    }
+
     fn initialise_from(config: InitialisedDeflateConfig, is_server: bool) -> Deflate {
         if is_server {
             Deflate {
                 decoder: DeflateDecoder::new(
-                    config.client_max_window_bits.0,
+                    config.client_max_window_bits,
                     config.decompress_reset,
+                    config.retain_state,
                 ),
                 encoder: DeflateEncoder::new(
                     config.compression_level,
-                    config.server_max_window_bits.0,
+                    config.server_max_window_bits,
                     config.compress_reset,
+                    config.retain_state,
+                    is_server,
                 ),
             }
         } else {
             Deflate {
                 decoder: DeflateDecoder::new(
-                    config.server_max_window_bits.0,
+                    config.server_max_window_bits,
                     config.decompress_reset,
+                    config.retain_state,
                 ),
                 encoder: DeflateEncoder::new(
                     config.compression_level,
-                    config.client_max_window_bits.0,
+                    config.client_max_window_bits,
                     config.compress_reset,
+                    config.retain_state,
+                    is_server,
                 ),
             }
         }
@@ -264,6 +314,131 @@ impl Extension for Deflate {
             rsv3: false,
         }
     }
+
+    fn stats(&self) -> Option<ExtensionStats> {
+        let Deflate { encoder, decoder } = self;
+        Some(ExtensionStats {
+            encoder_bytes_in: encoder.bytes_in,
+            encoder_bytes_out: encoder.bytes_out,
+            decoder_bytes_in: decoder.bytes_in,
+            decoder_bytes_out: decoder.bytes_out,
+        })
+    }
+
+    fn reset_stats(&mut self) {
+        let Deflate { encoder, decoder } = self;
+        encoder.bytes_in = 0;
+        encoder.bytes_out = 0;
+        decoder.bytes_in = 0;
+        decoder.bytes_out = 0;
+    }
+
+    fn summary(&self) -> Option<String> {
+        let ((server_bits, server_reset), (client_bits, client_reset)) = self.parameters();
+
+        let mut summary = format!(
+            "{}; {}={}; {}={}",
+            EXT_IDENT,
+            SERVER_MAX_BITS,
+            server_bits.as_str(),
+            CLIENT_MAX_BITS,
+            client_bits.as_str()
+        );
+        if server_reset {
+            summary.push_str("; ");
+            summary.push_str(SERVER_NO_TAKEOVER);
+        }
+        if client_reset {
+            summary.push_str("; ");
+            summary.push_str(CLIENT_NO_TAKEOVER);
+        }
+        Some(summary)
+    }
+}
+
+/// The saved state of a `Deflate` extension. When context takeover is in use, messages may
+/// reference data from previous messages and so this contains the most recent data that was
+/// compressed and decompressed, up to the size of the negotiated LZ77 windows.
+///
+/// The state of a session that uses context takeover may only be saved if
+/// `DeflateConfig::retain_state` was set.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeflateState {
+    /// The most recent data that was passed to the compressor.
+    pub encoder_window: Bytes,
+    /// The most recent data that was produced by the decompressor.
+    pub decoder_window: Bytes,
+}
+
+impl ExtensionState for Deflate {
+    type State = DeflateState;
+    type Error = DeflateExtensionError;
+
+    fn save(&self) -> Option<Self::State> {
+        let Deflate { encoder, decoder } = self;
+        let takeover = !encoder.compress_reset || !decoder.decompress_reset;
+
+        if takeover && !(encoder.retain_state && decoder.retain_state) {
+            // The windows are required to restore the session but they have not been retained.
+            None
+        } else {
+            Some(DeflateState {
+                encoder_window: Bytes::copy_from_slice(&encoder.history),
+                decoder_window: Bytes::copy_from_slice(&decoder.history),
+            })
+        }
+    }
+
+    fn restore(&mut self, state: Self::State) -> Result<(), DeflateExtensionError> {
+        let Deflate { encoder, decoder } = self;
+        let DeflateState {
+            encoder_window,
+            decoder_window,
+        } = state;
+
+        if !encoder_window.is_empty() {
+            let window = window(&encoder_window, encoder.window_bits);
+            encoder.compress.set_dictionary(window)?;
+            if encoder.retain_state {
+                extend_history(&mut encoder.history, window, encoder.window_bits);
+            }
+        }
+        if !decoder_window.is_empty() {
+            let window = window(&decoder_window, decoder.window_bits);
+            decoder.decompress.set_dictionary(window)?;
+            if decoder.retain_state {
+                extend_history(&mut decoder.history, window, decoder.window_bits);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the size of an LZ77 window of `window_bits`.
+fn window_size(window_bits: WindowBits) -> usize {
+    1 << window_bits.0.max(9)
+}
+
+/// Returns as many of the most recent bytes of `data` as will fit in an LZ77 window of
+/// `window_bits`.
+fn window(data: &[u8], window_bits: WindowBits) -> &[u8] {
+    &data[data.len().saturating_sub(window_size(window_bits))..]
+}
+
+/// Appends `data` to `history`, retaining only as many of the most recent bytes as will fit in an
+/// LZ77 window of `window_bits`.
+fn extend_history(history: &mut BytesMut, data: &[u8], window_bits: WindowBits) {
+    let window_size = window_size(window_bits);
+
+    if data.len() >= window_size {
+        history.clear();
+        history.extend_from_slice(window(data, window_bits));
+    } else {
+        history.extend_from_slice(data);
+        if history.len() > window_size {
+            history.advance(history.len() - window_size);
+        }
+    }
 }
 
 impl SplittableExtension for Deflate {
@@ -289,11 +464,26 @@ pub struct DeflateEncoder {
     buf: BytesMut,
     compress: Compress,
     compress_reset: bool,
+    window_bits: WindowBits,
+    is_server: bool,
+    retain_state: bool,
+    // The most recent data that was compressed, when context takeover is in use and the state is
+    // retained.
+    history: BytesMut,
+    bytes_in: u64,
+    bytes_out: u64,
 }
 
 impl DeflateEncoder {
-    fn new(compression: Compression, mut window_size: u8, compress_reset: bool) -> DeflateEncoder {
+    fn new(
+        compression: Compression,
+        window_bits: WindowBits,
+        compress_reset: bool,
+        retain_state: bool,
+        is_server: bool,
+    ) -> DeflateEncoder {
         // https://github.com/madler/zlib/blob/cacf7f1d4e3d44d871b605da3b647f07d718623f/deflate.c#L303
+        let mut window_size = window_bits.0;
         if window_size == 8 {
             window_size = 9;
         }
@@ -302,6 +492,12 @@ impl DeflateEncoder {
             buf: BytesMut::default(),
             compress: Compress::new_with_window_bits(compression, false, window_size),
             compress_reset,
+            window_bits,
+            is_server,
+            retain_state,
+            history: BytesMut::default(),
+            bytes_in: 0,
+            bytes_out: 0,
         }
     }
 }
@@ -334,6 +530,12 @@ impl ExtensionEncoder for DeflateEncoder {
             buf,
             compress,
             compress_reset,
+            window_bits,
+            retain_state,
+            history,
+            bytes_in,
+            bytes_out,
+            ..
         } = self;
 
         buf.clear();
@@ -361,8 +563,13 @@ impl ExtensionEncoder for DeflateEncoder {
         buf.truncate(buf.len() - DEFLATE_TRAILER.len());
         std::mem::swap(payload, buf);
 
+        *bytes_in += buf.len() as u64;
+        *bytes_out += payload.len() as u64;
+
         if *compress_reset {
             compress.reset();
+        } else if *retain_state {
+            extend_history(history, buf, *window_bits);
         }
 
         if !matches!(header.opcode, OpCode::Continuation) {
@@ -380,13 +587,21 @@ pub struct DeflateDecoder {
     buf: BytesMut,
     decompress: Decompress,
     decompress_reset: bool,
+    window_bits: WindowBits,
+    retain_state: bool,
+    // The most recent data that was decompressed, when context takeover is in use and the state
+    // is retained.
+    history: BytesMut,
     // Whether we're reading a compressed message
     compressed: bool,
+    bytes_in: u64,
+    bytes_out: u64,
 }
 
 impl DeflateDecoder {
-    fn new(mut window_size: u8, decompress_reset: bool) -> DeflateDecoder {
+    fn new(window_bits: WindowBits, decompress_reset: bool, retain_state: bool) -> DeflateDecoder {
         // https://github.com/madler/zlib/blob/cacf7f1d4e3d44d871b605da3b647f07d718623f/deflate.c#L303
+        let mut window_size = window_bits.0;
         if window_size == 8 {
             window_size = 9;
         }
@@ -395,7 +610,12 @@ impl DeflateDecoder {
             buf: BytesMut::default(),
             decompress: Decompress::new_with_window_bits(false, window_size),
             decompress_reset,
+            window_bits,
+            retain_state,
+            history: BytesMut::default(),
             compressed: false,
+            bytes_in: 0,
+            bytes_out: 0,
         }
     }
 }
@@ -424,13 +644,19 @@ impl ExtensionDecoder for DeflateDecoder {
             buf,
             decompress,
             decompress_reset,
+            window_bits,
+            retain_state,
+            history,
             compressed,
+            bytes_in,
+            bytes_out,
         } = self;
 
         match header.opcode {
             OpCode::Binary | OpCode::Text => {
                 *compressed = header.rsv1;
-                if !header.fin {
+                // Messages without RSV1 set were not compressed by the peer.
+                if !header.fin || !header.rsv1 {
                     return Ok(());
                 }
             }
@@ -457,8 +683,13 @@ impl ExtensionDecoder for DeflateDecoder {
         buf.truncate(buf.len());
         std::mem::swap(payload, buf);
 
+        *bytes_in += (buf.len() - DEFLATE_TRAILER.len()) as u64;
+        *bytes_out += payload.len() as u64;
+
         if *decompress_reset {
             decompress.reset(false);
+        } else if *retain_state {
+            extend_history(history, payload, *window_bits);
         }
 
         header.rsv1 = true;
@@ -13,12 +13,17 @@
 // limitations under the License.
 
 use crate::error::DeflateExtensionError;
-use crate::handshake::{apply_headers, on_request, on_response, NegotiationErr};
-use crate::{DeflateConfig, InitialisedDeflateConfig, WindowBits};
+use crate::handshake::{
+    apply_headers, negotiate_client, negotiate_server, on_request, on_response, NegotiationErr,
+};
+use crate::{Deflate, DeflateConfig, InitialisedDeflateConfig, WindowBits, DEFLATE_TRAILER};
+use bytes::BytesMut;
 use flate2::Compression;
 use http::header::SEC_WEBSOCKET_EXTENSIONS;
 use http::HeaderMap;
-use ratchet_ext::Header;
+use ratchet_ext::{
+    Extension, ExtensionDecoder, ExtensionEncoder, ExtensionStats, FrameHeader, Header, OpCode,
+};
 
 fn test_headers(config: DeflateConfig, expected: &str) {
     let mut header_map = HeaderMap::new();
@@ -45,6 +50,7 @@ fn applies_headers() {
             request_client_no_context_takeover: false,
             accept_no_context_takeover: false,
             compression_level: Default::default(),
+            retain_state: false,
         },
         "permessage-deflate; client_max_window_bits",
     );
@@ -56,6 +62,7 @@ fn applies_headers() {
             request_client_no_context_takeover: false,
             accept_no_context_takeover: false,
             compression_level: Default::default(),
+            retain_state: false,
         },
         "permessage-deflate; client_max_window_bits=8; server_max_window_bits=15",
     );
@@ -67,6 +74,7 @@ fn applies_headers() {
             request_client_no_context_takeover: true,
             accept_no_context_takeover: false,
             compression_level: Default::default(),
+            retain_state: false,
         },
         "permessage-deflate; client_max_window_bits=8; server_max_window_bits=15; server_no_context_takeover; client_no_context_takeover",
     );
@@ -78,6 +86,7 @@ fn applies_headers() {
             request_client_no_context_takeover: true,
             accept_no_context_takeover: false,
             compression_level: Default::default(),
+            retain_state: false,
         },
         "permessage-deflate; client_max_window_bits; server_no_context_takeover; client_no_context_takeover",
     );
@@ -89,6 +98,7 @@ fn applies_headers() {
             request_client_no_context_takeover: true,
             accept_no_context_takeover: false,
             compression_level: Default::default(),
+            retain_state: false,
         },
         "permessage-deflate; client_max_window_bits; client_no_context_takeover",
     );
@@ -117,7 +127,8 @@ fn request_test_valid_default(headers: &[Header]) {
                     client_max_window_bits: WindowBits::fifteen(),
                     compress_reset: true,
                     decompress_reset: true,
-                    compression_level: Compression::fast()
+                    compression_level: Compression::fast(),
+                    retain_state: false,
                 }
             )
         }
@@ -223,6 +234,7 @@ fn request_no_accept_no_context_takeover() {
         request_client_no_context_takeover: true,
         accept_no_context_takeover: false,
         compression_level: Compression::fast(),
+        retain_state: false,
     };
 
     match on_request(&[header], &config) {
@@ -236,7 +248,8 @@ fn request_no_accept_no_context_takeover() {
                     client_max_window_bits: WindowBits::fifteen(),
                     compress_reset: false,
                     decompress_reset: true,
-                    compression_level: Compression::fast()
+                    compression_level: Compression::fast(),
+                    retain_state: false,
                 }
             )
         }
@@ -371,3 +384,602 @@ fn response_no_context_takeover() {
         r => panic!("Expected an error. Got: {:?}", r),
     }
 }
+
+fn deflate_pair(compress_reset: bool) -> (Deflate, Deflate) {
+    let config = || InitialisedDeflateConfig {
+        server_max_window_bits: WindowBits::fifteen(),
+        client_max_window_bits: WindowBits::fifteen(),
+        compress_reset,
+        decompress_reset: compress_reset,
+        compression_level: Compression::fast(),
+        retain_state: false,
+    };
+    (
+        Deflate::initialise_from(config(), false),
+        Deflate::initialise_from(config(), true),
+    )
+}
+
+fn frame_header() -> FrameHeader {
+    FrameHeader {
+        fin: true,
+        rsv1: false,
+        rsv2: false,
+        rsv3: false,
+        opcode: OpCode::Binary,
+    }
+}
+
+#[test]
+fn stats() {
+    let (mut client, mut server) = deflate_pair(false);
+    let data = vec![b'a'; 10 * 1024];
+
+    let mut payload = BytesMut::from(data.as_slice());
+    let mut header = frame_header();
+    client.encode(&mut payload, &mut header).unwrap();
+
+    let stats = client.stats().unwrap();
+    assert_eq!(stats.encoder_bytes_in, data.len() as u64);
+    assert_eq!(stats.encoder_bytes_out, payload.len() as u64);
+    assert!(stats.bytes_out() < stats.bytes_in());
+
+    let compressed_len = payload.len() as u64;
+    server.decode(&mut payload, &mut header).unwrap();
+    assert_eq!(payload.as_ref(), data.as_slice());
+
+    let stats = server.stats().unwrap();
+    assert_eq!(stats.decoder_bytes_in, compressed_len);
+    assert_eq!(stats.decoder_bytes_out, data.len() as u64);
+
+    client.reset_stats();
+    assert_eq!(client.stats(), Some(ExtensionStats::default()));
+}
+
+fn negotiate(config: DeflateConfig) -> (Deflate, Deflate) {
+    let mut request_headers = HeaderMap::new();
+    apply_headers(&mut request_headers, &config);
+    let request_value = request_headers.get(SEC_WEBSOCKET_EXTENSIONS).unwrap();
+
+    let (server, response_value) = negotiate_server(
+        &[Header {
+            name: SEC_WEBSOCKET_EXTENSIONS.as_str(),
+            value: request_value.as_bytes(),
+        }],
+        &config,
+    )
+    .unwrap()
+    .expect("Negotiation failed");
+
+    let client = negotiate_client(
+        &[Header {
+            name: SEC_WEBSOCKET_EXTENSIONS.as_str(),
+            value: response_value.as_bytes(),
+        }],
+        &config,
+    )
+    .unwrap()
+    .expect("Negotiation failed");
+
+    (client, server)
+}
+
+fn round_trip(encoder: &mut Deflate, decoder: &mut Deflate, data: &[u8]) -> BytesMut {
+    let mut payload = BytesMut::from(data);
+    let mut header = frame_header();
+    encoder.encode(&mut payload, &mut header).unwrap();
+    let compressed = payload.clone();
+
+    decoder.decode(&mut payload, &mut header).unwrap();
+    assert_eq!(payload.as_ref(), data);
+
+    compressed
+}
+
+#[test]
+fn no_context_takeover_round_trip() {
+    let config = DeflateConfig {
+        request_server_no_context_takeover: true,
+        request_client_no_context_takeover: true,
+        accept_no_context_takeover: true,
+        ..Default::default()
+    };
+    let (mut client, mut server) = negotiate(config);
+
+    assert!(client.encoder.compress_reset && client.decoder.decompress_reset);
+    assert!(server.encoder.compress_reset && server.decoder.decompress_reset);
+
+    let data = "a highly compressible message. ".repeat(8);
+    let mut first_client = None;
+    let mut first_server = None;
+
+    for i in 0..50 {
+        let client_frame = round_trip(&mut client, &mut server, data.as_bytes());
+        let server_frame = round_trip(&mut server, &mut client, data.as_bytes());
+
+        // Without context takeover every message is compressed using a fresh LZ77 window and so
+        // identical messages produce identical frames.
+        assert_eq!(
+            first_client.get_or_insert(client_frame.clone()),
+            &client_frame
+        );
+        assert_eq!(
+            first_server.get_or_insert(server_frame.clone()),
+            &server_frame
+        );
+
+        let unique = format!("message {}", i);
+        round_trip(&mut client, &mut server, unique.as_bytes());
+        round_trip(&mut server, &mut client, unique.as_bytes());
+    }
+}
+
+#[test]
+fn compression_levels() {
+    let data = "a message that may be compressed at any level. ".repeat(32);
+    let mut sizes = Vec::new();
+
+    for compression_level in [
+        Compression::none(),
+        Compression::fast(),
+        Compression::best(),
+    ] {
+        let config = DeflateConfig {
+            compression_level,
+            ..Default::default()
+        };
+        let (mut client, mut server) = negotiate(config);
+
+        let client_frame = round_trip(&mut client, &mut server, data.as_bytes());
+        let server_frame = round_trip(&mut server, &mut client, data.as_bytes());
+        assert_eq!(client_frame, server_frame);
+
+        sizes.push(client_frame.len());
+    }
+
+    assert!(sizes[0] > data.len());
+    assert!(sizes[1] < data.len());
+    assert!(sizes[2] <= sizes[1]);
+}
+
+#[test]
+fn small_window_bits() {
+    // A block of pseudo-random data that is repeated at a distance which is further back than the
+    // smaller windows. The block itself is incompressible and so the data may only be compressed
+    // by referencing a previous repetition.
+    let block = (0..4096u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect::<Vec<_>>();
+    let data = block.repeat(16);
+
+    for bits in [
+        WindowBits::eight(),
+        WindowBits::ten(),
+        WindowBits::fifteen(),
+    ] {
+        let config = DeflateConfig {
+            server_max_window_bits: bits,
+            client_max_window_bits: bits,
+            ..Default::default()
+        };
+
+        let mut headers = HeaderMap::new();
+        apply_headers(&mut headers, &config);
+        let offer = headers[SEC_WEBSOCKET_EXTENSIONS].to_str().unwrap();
+        if bits < 15 {
+            assert!(offer.contains(&format!("server_max_window_bits={}", bits.as_str())));
+            assert!(offer.contains(&format!("client_max_window_bits={}", bits.as_str())));
+        }
+
+        let (mut client, mut server) = negotiate(config);
+
+        for _ in 0..3 {
+            let client_frame = round_trip(&mut client, &mut server, &data);
+            let server_frame = round_trip(&mut server, &mut client, &data);
+
+            // Only a full size window reaches back to the previous repetition of the block.
+            for frame in [client_frame, server_frame] {
+                if bits == 15 {
+                    assert!(frame.len() < block.len() * 2);
+                } else {
+                    assert!(frame.len() > data.len() / 2);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn inflates_with_other_backend() {
+    let (mut client, mut server) = deflate_pair(true);
+    let data = "a message compressed by one deflate implementation. ".repeat(16);
+
+    let mut payload = BytesMut::from(data.as_bytes());
+    let mut header = frame_header();
+    client.encode(&mut payload, &mut header).unwrap();
+    payload.extend_from_slice(&DEFLATE_TRAILER);
+    // Terminate the stream with an empty final block
+    payload.extend_from_slice(&[3, 0]);
+
+    let inflated = miniz_oxide::inflate::decompress_to_vec(&payload).unwrap();
+    assert_eq!(inflated, data.as_bytes());
+
+    let compressed = miniz_oxide::deflate::compress_to_vec(data.as_bytes(), 6);
+    let mut payload = BytesMut::from(compressed.as_slice());
+    let mut header = FrameHeader {
+        rsv1: true,
+        ..frame_header()
+    };
+    server.decode(&mut payload, &mut header).unwrap();
+    assert_eq!(payload.as_ref(), data.as_bytes());
+}
+
+#[tokio::test]
+async fn split_round_trip() {
+    use ratchet_core::{Message, NegotiatedExtension, Role, WebSocket, WebSocketConfig};
+
+    let (client_ext, server_ext) = negotiate(DeflateConfig::default());
+    let (client_stream, server_stream) = tokio::io::duplex(1024);
+
+    let client = WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        client_stream,
+        NegotiatedExtension::from(client_ext),
+        BytesMut::new(),
+        Role::Client,
+    );
+    let server = WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        server_stream,
+        NegotiatedExtension::from(server_ext),
+        BytesMut::new(),
+        Role::Server,
+    );
+
+    let (mut client_tx, mut client_rx) = client.split().unwrap();
+    let (mut server_tx, mut server_rx) = server.split().unwrap();
+
+    let data = "a compressed message. ".repeat(64);
+    let mut buf = BytesMut::new();
+
+    client_tx.write_text(&data).await.unwrap();
+    assert_eq!(server_rx.read(&mut buf).await.unwrap(), Message::Text);
+    assert_eq!(buf.as_ref(), data.as_bytes());
+    buf.clear();
+
+    server_tx.write_binary(&data).await.unwrap();
+    assert_eq!(client_rx.read(&mut buf).await.unwrap(), Message::Binary);
+    assert_eq!(buf.as_ref(), data.as_bytes());
+
+    let client = client_tx.reunite::<Deflate>(client_rx).unwrap();
+    let stats = client.extension_stats().unwrap();
+    assert_eq!(stats.encoder_bytes_in, data.len() as u64);
+    assert!(stats.encoder_bytes_out < stats.encoder_bytes_in);
+    assert_eq!(stats.decoder_bytes_out, data.len() as u64);
+}
+
+#[tokio::test]
+async fn rsv_bits() {
+    use ratchet_core::{
+        Message, NegotiatedExtension, ProtocolError, Role, WebSocket, WebSocketConfig,
+    };
+    use tokio::io::AsyncWriteExt;
+
+    async fn read_frame(rsv: u8) -> Result<(Message, BytesMut), ratchet_core::Error> {
+        let (client_ext, mut server_ext) = negotiate(DeflateConfig::default());
+        let (client_stream, mut peer) = tokio::io::duplex(1024);
+        let mut client = WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            client_stream,
+            NegotiatedExtension::from(client_ext),
+            BytesMut::new(),
+            Role::Client,
+        );
+
+        let mut payload = BytesMut::from("Hello");
+        server_ext
+            .encode(&mut payload, &mut frame_header())
+            .unwrap();
+
+        let mut frame = vec![0x81 | rsv, payload.len() as u8];
+        frame.extend_from_slice(&payload);
+        peer.write_all(&frame).await.unwrap();
+
+        let mut buf = BytesMut::new();
+        let message = client.read(&mut buf).await?;
+        Ok((message, buf))
+    }
+
+    // Deflate reserves RSV1.
+    let (message, buf) = read_frame(0x40).await.unwrap();
+    assert_eq!(message, Message::Text);
+    assert_eq!(buf.as_ref(), b"Hello");
+
+    for rsv in [0x40 | 0x20, 0x20, 0x10] {
+        let err = read_frame(rsv).await.expect_err("Expected a read failure");
+        assert_eq!(
+            err.downcast_ref::<ProtocolError>(),
+            Some(&ProtocolError::UnknownExtension)
+        );
+    }
+}
+
+#[tokio::test]
+async fn last_message_compressed() {
+    use ratchet_core::{Message, NegotiatedExtension, Role, WebSocket, WebSocketConfig};
+    use tokio::io::AsyncWriteExt;
+
+    let (client_ext, mut server_ext) = negotiate(DeflateConfig::default());
+    let (client_stream, mut peer) = tokio::io::duplex(1024);
+    let (_client_tx, mut client_rx) = WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        client_stream,
+        NegotiatedExtension::from(client_ext),
+        BytesMut::new(),
+        Role::Client,
+    )
+    .split()
+    .unwrap();
+    assert!(!client_rx.last_message_compressed());
+
+    let mut payload = BytesMut::from("compressed");
+    let mut header = frame_header();
+    server_ext.encode(&mut payload, &mut header).unwrap();
+    assert!(header.rsv1);
+
+    let mut frames = vec![0xC1, payload.len() as u8];
+    frames.extend_from_slice(&payload);
+    frames.extend_from_slice(&[0x81, 12]);
+    frames.extend_from_slice(b"uncompressed");
+    peer.write_all(&frames).await.unwrap();
+
+    let mut buf = BytesMut::new();
+    assert_eq!(client_rx.read(&mut buf).await.unwrap(), Message::Text);
+    assert_eq!(buf.as_ref(), b"compressed");
+    assert!(client_rx.last_message_compressed());
+    buf.clear();
+
+    assert_eq!(client_rx.read(&mut buf).await.unwrap(), Message::Text);
+    assert_eq!(buf.as_ref(), b"uncompressed");
+    assert!(!client_rx.last_message_compressed());
+}
+
+#[tokio::test]
+async fn negotiate_response_veto() {
+    use crate::DeflateExtProvider;
+    use http::HeaderValue;
+    use ratchet_core::WebSocketClientBuilder;
+    use ratchet_ext::ExtensionProvider;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Rejects any server that responds with a window larger than 10 bits.
+    struct SmallWindow(DeflateExtProvider);
+
+    impl ExtensionProvider for SmallWindow {
+        type Extension = Deflate;
+        type Error = DeflateExtensionError;
+
+        fn apply_headers(&self, headers: &mut HeaderMap) {
+            self.0.apply_headers(headers)
+        }
+
+        fn negotiate_client(
+            &self,
+            headers: &[Header],
+        ) -> Result<Option<Self::Extension>, Self::Error> {
+            self.0.negotiate_client(headers)
+        }
+
+        fn negotiate_response(
+            &self,
+            extension: Self::Extension,
+        ) -> Result<Option<Self::Extension>, Self::Error> {
+            if extension.server_max_window_bits() > WindowBits::ten() {
+                Err(DeflateExtensionError::InvalidMaxWindowBits)
+            } else {
+                Ok(Some(extension))
+            }
+        }
+
+        fn negotiate_server(
+            &self,
+            headers: &[Header],
+        ) -> Result<Option<(Self::Extension, HeaderValue)>, Self::Error> {
+            self.0.negotiate_server(headers)
+        }
+    }
+
+    async fn handshake(server_max_window_bits: u8) -> Result<bool, ratchet_core::Error> {
+        let (stream, mut peer) = tokio::io::duplex(1024);
+
+        let client = WebSocketClientBuilder::default()
+            .fixed_key(*b"the sample nonce")
+            .extension(SmallWindow(DeflateExtProvider::default()))
+            .subscribe(stream, "ws://127.0.0.1:9001/");
+        let server = async move {
+            let mut buf = BytesMut::with_capacity(1024);
+            peer.read_buf(&mut buf).await.unwrap();
+
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\nSec-WebSocket-Extensions: permessage-deflate; server_max_window_bits={}\r\n\r\n",
+                server_max_window_bits
+            );
+            peer.write_all(response.as_bytes()).await.unwrap();
+            peer
+        };
+
+        let (result, _peer) = tokio::join!(client, server);
+        result.map(|upgraded| upgraded.websocket.extension_stats().is_some())
+    }
+
+    assert!(handshake(10).await.unwrap());
+
+    let err = handshake(15)
+        .await
+        .expect_err("Expected the extension to be vetoed");
+    assert!(err.is_extension());
+    assert!(matches!(
+        err.downcast_ref::<DeflateExtensionError>(),
+        Some(DeflateExtensionError::InvalidMaxWindowBits)
+    ));
+}
+
+#[test]
+fn extensions_summary() {
+    use ratchet_core::{NegotiatedExtension, Role, WebSocket, WebSocketConfig};
+
+    fn summary<E: Extension>(extension: E, role: Role) -> String {
+        let (stream, _peer) = tokio::io::duplex(64);
+        WebSocket::from_upgraded(
+            WebSocketConfig::default(),
+            stream,
+            NegotiatedExtension::from(extension),
+            BytesMut::new(),
+            role,
+        )
+        .extensions_summary()
+    }
+
+    let (client, server) = negotiate(DeflateConfig {
+        client_max_window_bits: WindowBits::ten(),
+        request_client_no_context_takeover: false,
+        ..Default::default()
+    });
+    let expected = "permessage-deflate; server_max_window_bits=15; client_max_window_bits=10; server_no_context_takeover";
+    assert_eq!(summary(client, Role::Client), expected);
+    assert_eq!(summary(server, Role::Server), expected);
+}
+
+#[tokio::test]
+async fn restores_extension_state() {
+    use crate::DeflateState;
+    use ratchet_core::{Message, Role, WebSocket, WebSocketConfig};
+    use ratchet_ext::ExtensionState;
+    use tokio::io::AsyncWriteExt;
+
+    // Context takeover is used in both directions.
+    let config = DeflateConfig {
+        request_server_no_context_takeover: false,
+        request_client_no_context_takeover: false,
+        accept_no_context_takeover: false,
+        retain_state: true,
+        ..Default::default()
+    };
+    let (mut client_ext, mut server_ext) = negotiate(config);
+
+    let first = "a message which the next message will reference";
+    let mut payload = BytesMut::from(first);
+    server_ext
+        .encode(&mut payload, &mut frame_header())
+        .unwrap();
+    client_ext
+        .decode(
+            &mut payload,
+            &mut FrameHeader {
+                rsv1: true,
+                ..frame_header()
+            },
+        )
+        .unwrap();
+    assert_eq!(payload.as_ref(), first.as_bytes());
+
+    let state = client_ext.save().expect("Missing state");
+    assert_eq!(state.decoder_window.as_ref(), first.as_bytes());
+
+    // The state of a session using context takeover cannot be saved unless it was retained.
+    let (mut client, mut server) = negotiate(DeflateConfig {
+        retain_state: false,
+        ..config
+    });
+    round_trip(&mut server, &mut client, first.as_bytes());
+    assert_eq!(client.save(), None);
+    assert_eq!(server.save(), None);
+
+    // Without context takeover there is no state to retain.
+    let (mut client, mut server) = negotiate(DeflateConfig::default());
+    round_trip(&mut server, &mut client, first.as_bytes());
+    assert_eq!(client.save(), Some(DeflateState::default()));
+    assert_eq!(server.save(), Some(DeflateState::default()));
+
+    // The second message is encoded as a back-reference into the first.
+    let mut payload = BytesMut::from(first);
+    let mut header = frame_header();
+    server_ext.encode(&mut payload, &mut header).unwrap();
+    assert!(payload.len() < first.len() / 2);
+
+    let mut frame = vec![0xC2, payload.len() as u8];
+    frame.extend_from_slice(&payload);
+
+    async fn read(
+        config: DeflateConfig,
+        state: DeflateState,
+        frame: &[u8],
+    ) -> Result<(Message, BytesMut), ratchet_core::Error> {
+        let (client_ext, _) = negotiate(config);
+        let (stream, mut peer) = tokio::io::duplex(1024);
+        let mut client = WebSocket::from_upgraded_with_extension_state(
+            WebSocketConfig::default(),
+            stream,
+            client_ext,
+            state,
+            BytesMut::new(),
+            Role::Client,
+        )?;
+        peer.write_all(frame).await.unwrap();
+
+        let mut buf = BytesMut::new();
+        let message = client.read(&mut buf).await?;
+        Ok((message, buf))
+    }
+
+    let (message, buf) = read(config, state, &frame).await.unwrap();
+    assert_eq!(message, Message::Binary);
+    assert_eq!(buf.as_ref(), first.as_bytes());
+
+    // Without the state the back-reference cannot be resolved.
+    let err = read(config, DeflateState::default(), &frame)
+        .await
+        .expect_err("Expected a decode failure");
+    assert!(err.is_extension());
+}
+
+#[tokio::test]
+async fn byte_stream_interleaved_ping() {
+    use ratchet_core::{
+        NegotiatedExtension, Role, WebSocket, WebSocketByteStream, WebSocketConfig,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (client_ext, mut server_ext) = negotiate(DeflateConfig::default());
+
+    let data = "a compressed message which is sent in two fragments. ".repeat(8);
+    let mut payload = BytesMut::from(data.as_bytes());
+    server_ext
+        .encode(&mut payload, &mut frame_header())
+        .unwrap();
+    let (first, second) = payload.split_at(payload.len() / 2);
+    assert!(second.len() < 126);
+
+    // The first fragment has RSV1 set and is followed by a ping frame and then the final fragment.
+    let mut frames = vec![0x42, first.len() as u8];
+    frames.extend_from_slice(first);
+    frames.extend_from_slice(&[0x89, 0x04, b'p', b'i', b'n', b'g']);
+    frames.extend_from_slice(&[0x80, second.len() as u8]);
+    frames.extend_from_slice(second);
+    frames.extend_from_slice(&[0x88, 0x02, 0x03, 0xe8]);
+
+    let (stream, mut peer) = tokio::io::duplex(4096);
+    let client = WebSocket::from_upgraded(
+        WebSocketConfig::default(),
+        stream,
+        NegotiatedExtension::from(client_ext),
+        BytesMut::new(),
+        Role::Client,
+    );
+    let mut byte_stream = WebSocketByteStream::new(client).unwrap();
+    peer.write_all(&frames).await.unwrap();
+
+    let mut received = Vec::new();
+    byte_stream.read_to_end(&mut received).await.unwrap();
+    assert_eq!(received, data.as_bytes());
+}
@@ -67,6 +67,24 @@ pub trait ExtensionProvider {
     /// `to_string()` value.
     fn negotiate_client(&self, headers: &[Header]) -> Result<Option<Self::Extension>, Self::Error>;
 
+    /// Inspect the extension that was negotiated from the headers that the server responded with
+    /// before it is used by the client.
+    ///
+    /// This is invoked after `negotiate_client` has produced an extension and allows a provider that
+    /// wraps another provider to veto the parameters that it negotiated, which are well-formed but
+    /// unacceptable, without parsing the headers again; such as a window size that is larger than
+    /// it is willing to allocate. Returning `Ok(None)` continues the connection without the
+    /// extension.
+    ///
+    /// Returning `Err` from this will *fail* the connection with the reason being the error's
+    /// `to_string()` value. By default, the negotiated extension is accepted.
+    fn negotiate_response(
+        &self,
+        extension: Self::Extension,
+    ) -> Result<Option<Self::Extension>, Self::Error> {
+        Ok(Some(extension))
+    }
+
     /// Negotiate the headers that a client has sent.
     ///
     /// If it is possible to negotiate this extension, then this should return a pair containing an
@@ -84,7 +102,7 @@ pub trait ExtensionProvider {
     ) -> Result<Option<(Self::Extension, HeaderValue)>, Self::Error>;
 }
 
-impl<'r, E> ExtensionProvider for &'r mut E
+impl<E> ExtensionProvider for &mut E
 where
     E: ExtensionProvider,
 {
@@ -99,6 +117,13 @@ where
         E::negotiate_client(self, headers)
     }
 
+    fn negotiate_response(
+        &self,
+        extension: Self::Extension,
+    ) -> Result<Option<Self::Extension>, Self::Error> {
+        E::negotiate_response(self, extension)
+    }
+
     fn negotiate_server(
         &self,
         headers: &[Header],
@@ -107,7 +132,7 @@ where
     }
 }
 
-impl<'r, E> ExtensionProvider for &'r E
+impl<E> ExtensionProvider for &E
 where
     E: ExtensionProvider,
 {
@@ -122,6 +147,13 @@ where
         E::negotiate_client(self, headers)
     }
 
+    fn negotiate_response(
+        &self,
+        extension: Self::Extension,
+    ) -> Result<Option<Self::Extension>, Self::Error> {
+        E::negotiate_response(self, extension)
+    }
+
     fn negotiate_server(
         &self,
         headers: &[Header],
@@ -206,6 +238,48 @@ impl From<RsvBits> for u8 {
 pub trait Extension: ExtensionEncoder + ExtensionDecoder + Debug {
     /// Returns the reserved bits that this extension *may* set high during a session.
     fn bits(&self) -> RsvBits;
+
+    /// Returns the statistics that this extension has recorded, if it records any.
+    fn stats(&self) -> Option<ExtensionStats> {
+        None
+    }
+
+    /// Resets any statistics that this extension has recorded.
+    fn reset_stats(&mut self) {}
+
+    /// Returns a concise description of this extension and the parameters that were negotiated
+    /// for it, in the form of a `Sec-WebSocket-Extensions` header value. Such as
+    /// `permessage-deflate; server_max_window_bits=15; client_max_window_bits=15`.
+    ///
+    /// This is intended for logging and returns `None` if the extension does not describe itself.
+    fn summary(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Byte counts recorded by an extension.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ExtensionStats {
+    /// The number of bytes passed to the encoder.
+    pub encoder_bytes_in: u64,
+    /// The number of bytes produced by the encoder.
+    pub encoder_bytes_out: u64,
+    /// The number of bytes passed to the decoder.
+    pub decoder_bytes_in: u64,
+    /// The number of bytes produced by the decoder.
+    pub decoder_bytes_out: u64,
+}
+
+impl ExtensionStats {
+    /// Returns the total number of bytes passed to the extension.
+    pub fn bytes_in(&self) -> u64 {
+        self.encoder_bytes_in + self.decoder_bytes_in
+    }
+
+    /// Returns the total number of bytes produced by the extension.
+    pub fn bytes_out(&self) -> u64 {
+        self.encoder_bytes_out + self.decoder_bytes_out
+    }
 }
 
 /// A per-message frame encoder.
@@ -262,6 +336,26 @@ pub trait SplittableExtension: Extension {
     fn split(self) -> (Self::SplitEncoder, Self::SplitDecoder);
 }
 
+/// A trait for saving the state of a negotiated extension and restoring it into a new instance of
+/// the extension. Allowing for an active session to be handed off to another process, such as by a
+/// proxy, without the peer renegotiating the extension.
+pub trait ExtensionState: Extension {
+    /// The saved state of the extension.
+    type State;
+    /// The error type produced by this extension if its state cannot be restored.
+    type Error: Error + Send + Sync + 'static;
+
+    /// Returns the current state of this extension, or `None` if this extension has not retained
+    /// the state that is required to restore it.
+    fn save(&self) -> Option<Self::State>;
+
+    /// Restores `state` into this extension.
+    ///
+    /// This must only be invoked on an extension that was negotiated with the same parameters as
+    /// the extension that `state` was saved from and before it has encoded or decoded any frames.
+    fn restore(&mut self, state: Self::State) -> Result<(), <Self as ExtensionState>::Error>;
+}
+
 /// A trait for permitting a matched encoder and decoder to be reunited into an extension.
 pub trait ReunitableExtension: SplittableExtension {
     /// Reunite this encoder and decoder back into a single extension.
@@ -25,7 +25,7 @@ async fn subscribe(url: &str) -> Result<UpgradedClient<TcpStream, Deflate>, Erro
     stream.set_nodelay(true).unwrap();
 
     ratchet_rs::subscribe_with(
-        WebSocketConfig::default(),
+        WebSocketConfig::strict(),
         stream,
         url,
         &DeflateExtProvider::default(),
@@ -77,7 +77,6 @@ async fn run_test(case: u32) -> Result<(), Error> {
     loop {
         match websocket.read(&mut buf).await? {
             Message::Text => {
-                let _s = String::from_utf8(buf.to_vec())?;
                 websocket.write(&mut buf, PayloadType::Text).await?;
                 buf.clear();
             }
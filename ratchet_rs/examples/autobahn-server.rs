@@ -36,7 +36,7 @@ async fn accept(stream: TcpStream) {
 async fn run(stream: TcpStream) -> Result<(), Error> {
     let mut websocket = ratchet_rs::accept_with(
         stream,
-        WebSocketConfig::default(),
+        WebSocketConfig::strict(),
         DeflateExtProvider::default(),
         ProtocolRegistry::default(),
     )
@@ -51,7 +51,6 @@ async fn run(stream: TcpStream) -> Result<(), Error> {
     loop {
         match websocket.read(&mut buf).await? {
             Message::Text => {
-                let _s = String::from_utf8(buf.to_vec())?;
                 websocket.write(&mut buf, PayloadType::Text).await?;
                 buf.clear();
             }